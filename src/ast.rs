@@ -0,0 +1,486 @@
+use {Node, NodeType, NodeKind, ListType, DelimType, Document, NodeError};
+use builder::append_checked;
+
+/// How many levels deep `Block` and `Inline` trees may be, counting the root
+/// as the first. Converting a tree recurses once per level, as do the derived
+/// traits of `Block` and the writers built on it, so deeper trees are
+/// rejected rather than allowed to overflow the stack.
+pub const MAX_DEPTH: usize = 256;
+
+/// The range of source text a block was parsed from, as recorded by
+/// libcmark. Lines and columns count from 1, and the end is inclusive.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SourcePos {
+    pub start_line: i32,
+    pub start_column: i32,
+    pub end_line: i32,
+    pub end_column: i32,
+}
+
+impl SourcePos {
+    /// Get the position of `node`, or `None` if it has none.
+    pub fn of(node: &Node) -> Option<SourcePos> {
+        if node.start_line() == 0 {
+            return None;
+        }
+        Some(SourcePos {
+            start_line: node.start_line(),
+            start_column: node.start_column(),
+            end_line: node.end_line(),
+            end_column: node.end_column(),
+        })
+    }
+}
+
+/// An owned block node and its children, mirroring a libcmark tree in plain
+/// Rust values that can be pattern matched, compared and moved freely.
+///
+/// Converting a `Node` with `Block::from_node` takes a snapshot of the subtree,
+/// including every property and the source position of each block. libcmark
+/// has no way to set source positions, so `to_node` restores everything but
+/// those: the rebuilt nodes have no positions, and converting them back gives
+/// `pos: None` throughout.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, render_html, Block, Inline, DEFAULT};
+///
+/// let doc = parse_document("# Hello", DEFAULT);
+/// let mut ast = Block::from_node(&doc).unwrap();
+///
+/// if let Block::Document { ref mut children, .. } = ast {
+///     if let Block::Heading { ref mut level, ref mut children, .. } = children[0] {
+///         *level = 2;
+///         children.push(Inline::Emph(vec![Inline::Text(" world".to_string())]));
+///     }
+/// }
+///
+/// let rebuilt = ast.to_document().unwrap();
+/// assert_eq!(render_html(&rebuilt, DEFAULT), "<h2>Hello<em> world</em></h2>\n");
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum Block {
+    Document {
+        children: Vec<Block>,
+        pos: Option<SourcePos>,
+    },
+    BlockQuote {
+        children: Vec<Block>,
+        pos: Option<SourcePos>,
+    },
+    List {
+        list_type: ListType,
+        #[cfg_attr(feature = "serde", serde(rename = "list_delim"))]
+        delim: DelimType,
+        #[cfg_attr(feature = "serde", serde(rename = "list_start"))]
+        start: i32,
+        #[cfg_attr(feature = "serde", serde(rename = "list_tight"))]
+        tight: bool,
+        children: Vec<Block>,
+        pos: Option<SourcePos>,
+    },
+    Item {
+        children: Vec<Block>,
+        pos: Option<SourcePos>,
+    },
+    CodeBlock {
+        #[cfg_attr(feature = "serde", serde(rename = "fence_info"))]
+        info: String,
+        literal: String,
+        pos: Option<SourcePos>,
+    },
+    Html {
+        literal: String,
+        pos: Option<SourcePos>,
+    },
+    Paragraph {
+        children: Vec<Inline>,
+        pos: Option<SourcePos>,
+    },
+    #[cfg_attr(feature = "serde", serde(rename = "header"))]
+    Heading {
+        #[cfg_attr(feature = "serde", serde(rename = "header_level"))]
+        level: i32,
+        children: Vec<Inline>,
+        pos: Option<SourcePos>,
+    },
+    Hrule {
+        pos: Option<SourcePos>,
+    },
+}
+
+/// An owned inline node and its children. libcmark does not record source
+/// positions for inlines, so unlike `Block` these have none.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Inline {
+    Text(String),
+    SoftBreak,
+    LineBreak,
+    Code(String),
+    InlineHtml(String),
+    Emph(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Link {
+        url: String,
+        title: String,
+        children: Vec<Inline>,
+    },
+    Image {
+        url: String,
+        title: String,
+        children: Vec<Inline>,
+    },
+}
+
+impl Block {
+    /// Get the type of node this block corresponds to.
+    pub fn node_type(&self) -> NodeType {
+        match *self {
+            Block::Document { .. } => NodeType::Document,
+            Block::BlockQuote { .. } => NodeType::BlockQuote,
+            Block::List { .. } => NodeType::List,
+            Block::Item { .. } => NodeType::Item,
+            Block::CodeBlock { .. } => NodeType::CodeBlock,
+            Block::Html { .. } => NodeType::Html,
+            Block::Paragraph { .. } => NodeType::Paragraph,
+            Block::Heading { .. } => NodeType::Header,
+            Block::Hrule { .. } => NodeType::Hrule,
+        }
+    }
+
+    /// Get the source position of this block, if it has one.
+    pub fn pos(&self) -> Option<SourcePos> {
+        match *self {
+            Block::Document { pos, .. } |
+            Block::BlockQuote { pos, .. } |
+            Block::List { pos, .. } |
+            Block::Item { pos, .. } |
+            Block::CodeBlock { pos, .. } |
+            Block::Html { pos, .. } |
+            Block::Paragraph { pos, .. } |
+            Block::Heading { pos, .. } |
+            Block::Hrule { pos } => pos,
+        }
+    }
+
+    /// Build a libcmark tree from this block and its children. Source
+    /// positions are not restored.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NodeError` if a string contains a NUL byte, a header level
+    /// is out of range, or blocks are nested in a way libcmark does not
+    /// allow, such as an `Item` outside of a `List`. Returns
+    /// `NodeError::TooDeep` if blocks and inlines are nested more than
+    /// `MAX_DEPTH` levels deep.
+    pub fn to_node(&self) -> Result<Node, NodeError> {
+        self.to_node_at(1)
+    }
+
+    fn to_node_at(&self, depth: usize) -> Result<Node, NodeError> {
+        try!(check_depth(self.node_type(), depth));
+        let mut node = match *self {
+            Block::List { list_type, delim, start, tight, .. } =>
+                try!(Node::list(list_type, delim, start, tight)),
+            Block::CodeBlock { ref info, ref literal, .. } =>
+                try!(Node::code_block(info, literal)),
+            Block::Html { ref literal, .. } => {
+                let mut node = Node::new(NodeType::Html);
+                try!(node.set_literal(literal));
+                node
+            }
+            Block::Heading { level, .. } => try!(Node::header(level, Vec::new())),
+            _ => Node::new(self.node_type()),
+        };
+
+        match *self {
+            Block::Document { ref children, .. } |
+            Block::BlockQuote { ref children, .. } |
+            Block::List { ref children, .. } |
+            Block::Item { ref children, .. } => {
+                for child in children {
+                    try!(append_checked(&mut node, try!(child.to_node_at(depth + 1))));
+                }
+            }
+            Block::Paragraph { ref children, .. } |
+            Block::Heading { ref children, .. } => {
+                for child in children {
+                    try!(append_checked(&mut node, try!(child.to_node_at(depth + 1))));
+                }
+            }
+            _ => (),
+        }
+        Ok(node)
+    }
+
+    /// Build a `Document` from this block, as with `to_node`.
+    pub fn to_document(&self) -> Result<Document, NodeError> {
+        self.to_node().map(Document::from)
+    }
+}
+
+impl Inline {
+    /// Get the type of node this inline corresponds to.
+    pub fn node_type(&self) -> NodeType {
+        match *self {
+            Inline::Text(..) => NodeType::Text,
+            Inline::SoftBreak => NodeType::SoftBreak,
+            Inline::LineBreak => NodeType::LineBreak,
+            Inline::Code(..) => NodeType::Code,
+            Inline::InlineHtml(..) => NodeType::InlineHtml,
+            Inline::Emph(..) => NodeType::Emph,
+            Inline::Strong(..) => NodeType::Strong,
+            Inline::Link { .. } => NodeType::Link,
+            Inline::Image { .. } => NodeType::Image,
+        }
+    }
+
+    /// Build a libcmark tree from this inline and its children.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `NodeError` if a string contains a NUL byte, or
+    /// `NodeError::TooDeep` if inlines are nested more than `MAX_DEPTH`
+    /// levels deep.
+    pub fn to_node(&self) -> Result<Node, NodeError> {
+        self.to_node_at(1)
+    }
+
+    fn to_node_at(&self, depth: usize) -> Result<Node, NodeError> {
+        try!(check_depth(self.node_type(), depth));
+        let children = match *self {
+            Inline::Text(ref literal) => return Node::text(literal),
+            Inline::Code(ref literal) => return Node::code(literal),
+            Inline::InlineHtml(ref literal) => {
+                let mut node = Node::new(NodeType::InlineHtml);
+                try!(node.set_literal(literal));
+                return Ok(node);
+            }
+            Inline::SoftBreak | Inline::LineBreak => return Ok(Node::new(self.node_type())),
+            Inline::Emph(ref children) | Inline::Strong(ref children) => children,
+            Inline::Link { ref children, .. } | Inline::Image { ref children, .. } => children,
+        };
+
+        let nodes: Vec<Node> = try!(children.iter().map(|child| child.to_node_at(depth + 1)).collect());
+        match *self {
+            Inline::Link { ref url, ref title, .. } => Node::link(url, title, nodes),
+            Inline::Image { ref url, ref title, .. } => Node::image(url, title, nodes),
+            _ => {
+                let mut node = Node::new(self.node_type());
+                for child in nodes {
+                    try!(append_checked(&mut node, child));
+                }
+                Ok(node)
+            }
+        }
+    }
+}
+
+impl Block {
+    /// Snapshot `node` and its descendants.
+    ///
+    /// # Errors
+    ///
+    /// If `node` is not a block node; see `NodeType::is_block`. The error is
+    /// `NodeError::UnexpectedType`, expecting a `Document`. Also returns
+    /// `NodeError::TooDeep` if the subtree is more than `MAX_DEPTH` levels
+    /// deep.
+    pub fn from_node(node: &Node) -> Result<Block, NodeError> {
+        Block::from_node_at(node, 1)
+    }
+
+    fn from_node_at(node: &Node, depth: usize) -> Result<Block, NodeError> {
+        try!(check_depth(node.node_type(), depth));
+        let pos = SourcePos::of(node);
+        Ok(match node.kind() {
+            NodeKind::Document => Block::Document {
+                children: try!(blocks(node, depth)),
+                pos: pos,
+            },
+            NodeKind::BlockQuote => Block::BlockQuote {
+                children: try!(blocks(node, depth)),
+                pos: pos,
+            },
+            NodeKind::List(list) => Block::List {
+                list_type: list.list_type(),
+                delim: list.delim(),
+                start: list.start(),
+                tight: list.tight(),
+                children: try!(blocks(node, depth)),
+                pos: pos,
+            },
+            NodeKind::Item => Block::Item {
+                children: try!(blocks(node, depth)),
+                pos: pos,
+            },
+            NodeKind::CodeBlock(code) => Block::CodeBlock {
+                info: code.fence_info().to_string(),
+                literal: code.literal().to_string(),
+                pos: pos,
+            },
+            NodeKind::Html(html) => Block::Html {
+                literal: html.literal().to_string(),
+                pos: pos,
+            },
+            NodeKind::Paragraph => Block::Paragraph {
+                children: try!(inlines(node, depth)),
+                pos: pos,
+            },
+            NodeKind::Header(header) => Block::Heading {
+                level: header.level(),
+                children: try!(inlines(node, depth)),
+                pos: pos,
+            },
+            NodeKind::Hrule => Block::Hrule {
+                pos: pos,
+            },
+            _ => return Err(NodeError::UnexpectedType {
+                expected: NodeType::Document,
+                found: node.node_type(),
+            }),
+        })
+    }
+}
+
+impl Inline {
+    /// Snapshot `node` and its descendants.
+    ///
+    /// # Errors
+    ///
+    /// If `node` is not an inline node; see `NodeType::is_inline`. The error
+    /// is `NodeError::UnexpectedType`, expecting a `Text`. Also returns
+    /// `NodeError::TooDeep` if the subtree is more than `MAX_DEPTH` levels
+    /// deep.
+    pub fn from_node(node: &Node) -> Result<Inline, NodeError> {
+        Inline::from_node_at(node, 1)
+    }
+
+    fn from_node_at(node: &Node, depth: usize) -> Result<Inline, NodeError> {
+        try!(check_depth(node.node_type(), depth));
+        Ok(match node.kind() {
+            NodeKind::Text(text) => Inline::Text(text.literal().to_string()),
+            NodeKind::SoftBreak => Inline::SoftBreak,
+            NodeKind::LineBreak => Inline::LineBreak,
+            NodeKind::Code(code) => Inline::Code(code.literal().to_string()),
+            NodeKind::InlineHtml(html) => Inline::InlineHtml(html.literal().to_string()),
+            NodeKind::Emph => Inline::Emph(try!(inlines(node, depth))),
+            NodeKind::Strong => Inline::Strong(try!(inlines(node, depth))),
+            NodeKind::Link(link) => Inline::Link {
+                url: link.url().to_string(),
+                title: link.title().to_string(),
+                children: try!(inlines(node, depth)),
+            },
+            NodeKind::Image(image) => Inline::Image {
+                url: image.url().to_string(),
+                title: image.title().to_string(),
+                children: try!(inlines(node, depth)),
+            },
+            _ => return Err(NodeError::UnexpectedType {
+                expected: NodeType::Text,
+                found: node.node_type(),
+            }),
+        })
+    }
+}
+
+/// Snapshot the children of `node`, which is at `depth`.
+fn blocks(node: &Node, depth: usize) -> Result<Vec<Block>, NodeError> {
+    node.children().map(|child| Block::from_node_at(&child, depth + 1)).collect()
+}
+
+fn inlines(node: &Node, depth: usize) -> Result<Vec<Inline>, NodeError> {
+    node.children().map(|child| Inline::from_node_at(&child, depth + 1)).collect()
+}
+
+fn check_depth(node_type: NodeType, depth: usize) -> Result<(), NodeError> {
+    if depth > MAX_DEPTH {
+        Err(NodeError::TooDeep {
+            node_type: node_type,
+            limit: MAX_DEPTH,
+        })
+    }
+    else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, Positions, NodeType, NodeError, DEFAULT};
+
+    #[test]
+    fn test_round_trip() {
+        let source = "# Head *em* **st**\n\n\
+                      > 1. [link](/u \"t\") ![img](/i)\n\
+                      >    `code` <b>\\\n\
+                      >    soft\n\
+                      \n\
+                      ```rust\nfn main() {}\n```\n\n\
+                      <div>\n\n\
+                      ---\n";
+        let doc = parse_document(source, DEFAULT);
+        let ast = Block::from_node(&doc).unwrap();
+        let rebuilt = ast.to_document().unwrap();
+        assert!(doc.structural_eq(&rebuilt, Positions::Ignore));
+        assert!(!doc.structural_eq(&rebuilt, Positions::Include));
+
+        match ast {
+            Block::Document { ref children, pos } => {
+                assert_eq!(children.len(), 5);
+                assert_eq!(pos.unwrap().start_line, 1);
+                assert_eq!(children[0].pos(), Some(SourcePos {
+                    start_line: 1,
+                    start_column: 1,
+                    end_line: 1,
+                    end_column: 18,
+                }));
+            }
+            _ => panic!("expected a document"),
+        }
+        assert_eq!(Block::from_node(&rebuilt).unwrap().pos(), None);
+    }
+
+    #[test]
+    fn test_wrong_kind() {
+        let doc = parse_document("*a*", DEFAULT);
+        let emph = doc.first_child().unwrap().first_child().unwrap();
+        assert_eq!(Block::from_node(&emph),
+                   Err(NodeError::UnexpectedType { expected: NodeType::Document,
+                                                   found: NodeType::Emph }));
+        assert_eq!(Inline::from_node(&doc),
+                   Err(NodeError::UnexpectedType { expected: NodeType::Text,
+                                                   found: NodeType::Document }));
+        assert_eq!(Inline::from_node(&emph),
+                   Ok(Inline::Emph(vec![Inline::Text("a".to_string())])));
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let doc = parse_document(&"> ".repeat(50000), DEFAULT);
+        assert_eq!(Block::from_node(&doc),
+                   Err(NodeError::TooDeep { node_type: NodeType::BlockQuote, limit: MAX_DEPTH }));
+
+        let mut ast = Block::Paragraph { children: vec![], pos: None };
+        for _ in 0..MAX_DEPTH {
+            ast = Block::BlockQuote { children: vec![ast], pos: None };
+        }
+        assert_eq!(ast.to_node().err(),
+                   Some(NodeError::TooDeep { node_type: NodeType::Paragraph, limit: MAX_DEPTH }));
+    }
+
+    #[test]
+    fn test_invalid_nesting() {
+        let ast = Block::Document {
+            children: vec![Block::Item { children: vec![], pos: None }],
+            pos: None,
+        };
+        assert_eq!(ast.to_node().err(),
+                   Some(NodeError::InvalidChild { parent: NodeType::Document,
+                                                  child: NodeType::Item }));
+    }
+}
@@ -0,0 +1,319 @@
+use {Node, NodeRef};
+use node::handle;
+use util::Binding;
+
+use std::iter::Rev;
+
+/// A run of consecutive siblings, produced by `children`,
+/// `following_siblings` and friends. It can be walked from either end.
+pub struct Siblings<'a> {
+    front: Option<NodeRef<'a>>,
+    back: Option<NodeRef<'a>>,
+}
+
+impl<'a> Iterator for Siblings<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<NodeRef<'a>> {
+        let node = match self.front.take() {
+            Some(node) => node,
+            None => return None,
+        };
+        if same_node(&self.back, &node) {
+            self.back = None;
+        }
+        else {
+            self.front = node.next();
+        }
+        Some(node)
+    }
+}
+
+impl<'a> DoubleEndedIterator for Siblings<'a> {
+    fn next_back(&mut self) -> Option<NodeRef<'a>> {
+        let node = match self.back.take() {
+            Some(node) => node,
+            None => return None,
+        };
+        if same_node(&self.front, &node) {
+            self.front = None;
+        }
+        else {
+            self.back = node.previous();
+        }
+        Some(node)
+    }
+}
+
+/// The parent, grandparent and so on of a node, nearest first.
+pub struct Ancestors<'a> {
+    next: Option<NodeRef<'a>>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<NodeRef<'a>> {
+        self.next.take().map(|node| {
+            self.next = node.parent();
+            node
+        })
+    }
+}
+
+/// All of the nodes below a node in document order, without the `Exit`
+/// events of a `NodeIterator`. It can also be walked backwards, in reverse
+/// document order.
+pub struct Descendants<'a> {
+    root: NodeRef<'a>,
+    front: Option<NodeRef<'a>>,
+    back: Option<NodeRef<'a>>,
+}
+
+impl<'a> Descendants<'a> {
+    /// The node after `node` in document order, staying below the root.
+    fn following(&self, node: &NodeRef<'a>) -> Option<NodeRef<'a>> {
+        if let Some(child) = node.first_child() {
+            return Some(child);
+        }
+
+        let mut current = node.clone();
+        while current.raw() != self.root.raw() {
+            if let Some(sibling) = current.next() {
+                return Some(sibling);
+            }
+            current = match current.parent() {
+                Some(parent) => parent,
+                None => return None,
+            };
+        }
+        None
+    }
+
+    /// The node before `node` in document order, staying below the root.
+    fn preceding(&self, node: &NodeRef<'a>) -> Option<NodeRef<'a>> {
+        match node.previous() {
+            Some(sibling) => Some(last_descendant(sibling)),
+            None => node.parent().and_then(|parent| {
+                if parent.raw() == self.root.raw() { None } else { Some(parent) }
+            }),
+        }
+    }
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = NodeRef<'a>;
+
+    fn next(&mut self) -> Option<NodeRef<'a>> {
+        let node = match self.front.take() {
+            Some(node) => node,
+            None => return None,
+        };
+        if same_node(&self.back, &node) {
+            self.back = None;
+        }
+        else {
+            self.front = self.following(&node);
+        }
+        Some(node)
+    }
+}
+
+impl<'a> DoubleEndedIterator for Descendants<'a> {
+    fn next_back(&mut self) -> Option<NodeRef<'a>> {
+        let node = match self.back.take() {
+            Some(node) => node,
+            None => return None,
+        };
+        if same_node(&self.front, &node) {
+            self.front = None;
+        }
+        else {
+            self.back = self.preceding(&node);
+        }
+        Some(node)
+    }
+}
+
+fn same_node(a: &Option<NodeRef>, b: &NodeRef) -> bool {
+    a.as_ref().map_or(false, |a| a.raw() == b.raw())
+}
+
+fn last_descendant<'a>(node: NodeRef<'a>) -> NodeRef<'a> {
+    let mut current = node;
+    while let Some(child) = current.last_child() {
+        current = child;
+    }
+    current
+}
+
+fn last_sibling<'a>(node: NodeRef<'a>) -> NodeRef<'a> {
+    if let Some(last) = node.parent().and_then(|parent| parent.last_child()) {
+        return last;
+    }
+    let mut current = node;
+    while let Some(sibling) = current.next() {
+        current = sibling;
+    }
+    current
+}
+
+fn first_sibling<'a>(node: NodeRef<'a>) -> NodeRef<'a> {
+    if let Some(first) = node.parent().and_then(|parent| parent.first_child()) {
+        return first;
+    }
+    let mut current = node;
+    while let Some(sibling) = current.previous() {
+        current = sibling;
+    }
+    current
+}
+
+impl<'a> NodeRef<'a> {
+    /// Iterate over the children of this node, first to last.
+    pub fn children(&self) -> Siblings<'a> {
+        Siblings {
+            front: self.first_child(),
+            back: self.last_child(),
+        }
+    }
+
+    /// Iterate over the children of this node, last to first.
+    pub fn children_rev(&self) -> Rev<Siblings<'a>> {
+        self.children().rev()
+    }
+
+    /// Iterate over the parent of this node and its ancestors, nearest first.
+    pub fn ancestors(&self) -> Ancestors<'a> {
+        Ancestors {
+            next: self.parent(),
+        }
+    }
+
+    /// Iterate over every node below this one in document order.
+    pub fn descendants(&self) -> Descendants<'a> {
+        let first = self.first_child();
+        let last = self.last_child().map(last_descendant);
+        Descendants {
+            root: self.clone(),
+            front: first,
+            back: last,
+        }
+    }
+
+    /// Iterate over the siblings after this node, nearest first.
+    pub fn following_siblings(&self) -> Siblings<'a> {
+        let front = self.next();
+        let back = front.clone().map(last_sibling);
+        Siblings {
+            front: front,
+            back: back,
+        }
+    }
+
+    /// Iterate over the siblings before this node, nearest first.
+    pub fn preceding_siblings(&self) -> Rev<Siblings<'a>> {
+        let back = self.previous();
+        let front = back.clone().map(first_sibling);
+        Siblings {
+            front: front,
+            back: back,
+        }.rev()
+    }
+}
+
+impl Node {
+    /// Iterate over the children of this node, first to last.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, NodeType, DEFAULT};
+    ///
+    /// let doc = parse_document("# One\n\nTwo\n\n---", DEFAULT);
+    /// let types: Vec<_> = doc.children().map(|n| n.node_type()).collect();
+    /// assert_eq!(types, vec![NodeType::Header, NodeType::Paragraph, NodeType::Hrule]);
+    /// ```
+    pub fn children(&self) -> Siblings {
+        handle(self).children()
+    }
+
+    /// Iterate over the children of this node, last to first.
+    pub fn children_rev(&self) -> Rev<Siblings> {
+        handle(self).children_rev()
+    }
+
+    /// Iterate over the parent of this node and its ancestors, nearest first.
+    pub fn ancestors(&self) -> Ancestors {
+        handle(self).ancestors()
+    }
+
+    /// Iterate over every node below this one in document order, visiting
+    /// each node once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, NodeType, DEFAULT};
+    ///
+    /// let doc = parse_document("*a* b", DEFAULT);
+    /// let types: Vec<_> = doc.descendants().map(|n| n.node_type()).collect();
+    /// assert_eq!(types, vec![NodeType::Paragraph, NodeType::Emph,
+    ///                        NodeType::Text, NodeType::Text]);
+    /// ```
+    pub fn descendants(&self) -> Descendants {
+        handle(self).descendants()
+    }
+
+    /// Iterate over the siblings after this node, nearest first.
+    pub fn following_siblings(&self) -> Siblings {
+        handle(self).following_siblings()
+    }
+
+    /// Iterate over the siblings before this node, nearest first.
+    pub fn preceding_siblings(&self) -> Rev<Siblings> {
+        handle(self).preceding_siblings()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{parse_document, NodeType, DEFAULT};
+
+    fn literals<'a, I: Iterator<Item = ::NodeRef<'a>>>(iter: I) -> Vec<String> {
+        iter.filter(|n| n.node_type() == NodeType::Text)
+            .map(|n| n.literal().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_descendants_both_ways() {
+        let doc = parse_document("* a\n* *b* c\n\nd", DEFAULT);
+        assert_eq!(literals(doc.descendants()), vec!["a", "b", " c", "d"]);
+        assert_eq!(literals(doc.descendants().rev()), vec!["d", " c", "b", "a"]);
+
+        let mut iter = doc.descendants();
+        let first = iter.next().unwrap();
+        let last = iter.next_back().unwrap();
+        assert_eq!(first.node_type(), NodeType::List);
+        assert_eq!(last.literal(), "d");
+        assert_eq!(iter.count(), 9);
+    }
+
+    #[test]
+    fn test_siblings() {
+        let doc = parse_document("a *b* `c` **d**", DEFAULT);
+        let paragraph = doc.first_child().unwrap();
+        let code = paragraph.children().nth(3).unwrap();
+        assert_eq!(code.node_type(), NodeType::Code);
+
+        let after: Vec<_> = code.following_siblings().map(|n| n.node_type()).collect();
+        assert_eq!(after, vec![NodeType::Text, NodeType::Strong]);
+        let before: Vec<_> = code.preceding_siblings().map(|n| n.node_type()).collect();
+        assert_eq!(before, vec![NodeType::Text, NodeType::Emph, NodeType::Text]);
+
+        let ancestors: Vec<_> = code.ancestors().map(|n| n.node_type()).collect();
+        assert_eq!(ancestors, vec![NodeType::Paragraph, NodeType::Document]);
+        assert_eq!(paragraph.children_rev().next().unwrap().node_type(), NodeType::Strong);
+    }
+}
@@ -0,0 +1,446 @@
+use {Node, NodeType, ListType, DelimType, Document, NodeError};
+
+impl Node {
+    /// Create a `Text` node with the given contents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::Node;
+    ///
+    /// let text = Node::text("Hello").unwrap();
+    /// assert_eq!(text.literal(), "Hello");
+    /// ```
+    pub fn text(literal: &str) -> Result<Node, NodeError> {
+        literal_node(NodeType::Text, literal)
+    }
+
+    /// Create an inline `Code` node with the given contents.
+    pub fn code(literal: &str) -> Result<Node, NodeError> {
+        literal_node(NodeType::Code, literal)
+    }
+
+    /// Create a `Header` of the given level containing `children`, which must
+    /// all be inlines.
+    pub fn header(level: i32, children: Vec<Node>) -> Result<Node, NodeError> {
+        let mut node = Node::new(NodeType::Header);
+        try!(node.set_header_level(level));
+        try!(append_all(&mut node, children));
+        Ok(node)
+    }
+
+    /// Create a `Link` to `url` containing `children`, which must all be
+    /// inlines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::Node;
+    ///
+    /// let link = Node::link("http://commonmark.org", "",
+    ///                       vec![Node::text("CommonMark").unwrap()]).unwrap();
+    /// assert_eq!(link.first_child().unwrap().literal(), "CommonMark");
+    /// ```
+    pub fn link(url: &str, title: &str, children: Vec<Node>) -> Result<Node, NodeError> {
+        link_node(NodeType::Link, url, title, children)
+    }
+
+    /// Create an `Image` of `url`, whose `children` form its alt text.
+    pub fn image(url: &str, title: &str, children: Vec<Node>) -> Result<Node, NodeError> {
+        link_node(NodeType::Image, url, title, children)
+    }
+
+    /// Create a `CodeBlock` with the given info string and contents.
+    pub fn code_block(info: &str, literal: &str) -> Result<Node, NodeError> {
+        let mut node = Node::new(NodeType::CodeBlock);
+        try!(node.set_fence_info(info));
+        try!(node.set_literal(literal));
+        Ok(node)
+    }
+
+    /// Create an empty `List`. Its children must be `Item` nodes.
+    pub fn list(list_type: ListType, delim: DelimType, start: i32, tight: bool)
+                -> Result<Node, NodeError> {
+        let mut node = Node::new(NodeType::List);
+        try!(node.set_list_type(list_type));
+        try!(node.set_list_delim(delim));
+        try!(node.set_list_start(start));
+        try!(node.set_list_tight(tight));
+        Ok(node)
+    }
+}
+
+fn literal_node(node_type: NodeType, literal: &str) -> Result<Node, NodeError> {
+    let mut node = Node::new(node_type);
+    try!(node.set_literal(literal));
+    Ok(node)
+}
+
+fn link_node(node_type: NodeType, url: &str, title: &str, children: Vec<Node>)
+             -> Result<Node, NodeError> {
+    let mut node = Node::new(node_type);
+    try!(node.set_url(url));
+    try!(node.set_title(title));
+    try!(append_all(&mut node, children));
+    Ok(node)
+}
+
+fn append_all(parent: &mut Node, children: Vec<Node>) -> Result<(), NodeError> {
+    for child in children {
+        try!(append_checked(parent, child));
+    }
+    Ok(())
+}
+
+/// Append `child` to `parent`, checking that the nesting is valid and that
+/// libcmark accepted the new child.
+pub fn append_checked(parent: &mut Node, child: Node) -> Result<(), NodeError> {
+    let parent_type = parent.node_type();
+    let child_type = child.node_type();
+    if parent_type.can_contain(child_type) && parent.append_child(child).is_ok() {
+        Ok(())
+    }
+    else {
+        Err(NodeError::InvalidChild {
+            parent: parent_type,
+            child: child_type,
+        })
+    }
+}
+
+/// Builds a `Document` one block at a time. Each kind of container is filled
+/// by its own builder type, so blocks can only be added where blocks are
+/// allowed, items only inside lists and inlines only inside paragraphs,
+/// headers and other inlines. The first error encountered, such as a string
+/// containing a NUL byte, is reported by `build`.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{DocumentBuilder, ListType, DelimType, render_html, DEFAULT};
+///
+/// let doc = DocumentBuilder::new()
+///     .heading(2, |h| h.text("Install"))
+///     .paragraph(|p| p.text("Run ").code("cargo build"))
+///     .list(ListType::Bullet, DelimType::NoDelim, 1, true, |l| {
+///         l.item(|i| i.paragraph(|p| p.emph(|e| e.text("Done"))))
+///     })
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(render_html(&doc, DEFAULT),
+///            "<h2>Install</h2>\n\
+///             <p>Run <code>cargo build</code></p>\n\
+///             <ul>\n<li><em>Done</em></li>\n</ul>\n");
+/// ```
+pub struct DocumentBuilder {
+    blocks: BlockBuilder,
+}
+
+impl DocumentBuilder {
+    /// Start building an empty document.
+    pub fn new() -> DocumentBuilder {
+        DocumentBuilder {
+            blocks: BlockBuilder::new(Node::new(NodeType::Document)),
+        }
+    }
+
+    /// Add a header of the given level.
+    pub fn heading<F>(&mut self, level: i32, f: F) -> &mut DocumentBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        self.blocks.heading(level, f);
+        self
+    }
+
+    /// Add a paragraph.
+    pub fn paragraph<F>(&mut self, f: F) -> &mut DocumentBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        self.blocks.paragraph(f);
+        self
+    }
+
+    /// Add a block quote.
+    pub fn block_quote<F>(&mut self, f: F) -> &mut DocumentBuilder
+        where F: FnOnce(&mut BlockBuilder) -> &mut BlockBuilder {
+        self.blocks.block_quote(f);
+        self
+    }
+
+    /// Add a list.
+    pub fn list<F>(&mut self, list_type: ListType, delim: DelimType, start: i32, tight: bool,
+                   f: F) -> &mut DocumentBuilder
+        where F: FnOnce(&mut ListBuilder) -> &mut ListBuilder {
+        self.blocks.list(list_type, delim, start, tight, f);
+        self
+    }
+
+    /// Add a code block.
+    pub fn code_block(&mut self, info: &str, literal: &str) -> &mut DocumentBuilder {
+        self.blocks.code_block(info, literal);
+        self
+    }
+
+    /// Add a raw HTML block.
+    pub fn html(&mut self, literal: &str) -> &mut DocumentBuilder {
+        self.blocks.html(literal);
+        self
+    }
+
+    /// Add a horizontal rule.
+    pub fn hrule(&mut self) -> &mut DocumentBuilder {
+        self.blocks.hrule();
+        self
+    }
+
+    /// Add an existing block node.
+    pub fn node(&mut self, node: Node) -> &mut DocumentBuilder {
+        self.blocks.node(node);
+        self
+    }
+
+    /// Finish the document, or return the first error encountered while
+    /// building it. The builder is left empty.
+    pub fn build(&mut self) -> Result<Document, NodeError> {
+        let blocks = ::std::mem::replace(&mut self.blocks,
+                                         BlockBuilder::new(Node::new(NodeType::Document)));
+        blocks.finish().map(Document::from)
+    }
+}
+
+/// Fills a container of blocks: the document, a block quote or a list item.
+pub struct BlockBuilder {
+    node: Node,
+    error: Option<NodeError>,
+}
+
+impl BlockBuilder {
+    fn new(node: Node) -> BlockBuilder {
+        BlockBuilder {
+            node: node,
+            error: None,
+        }
+    }
+
+    fn finish(self) -> Result<Node, NodeError> {
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.node),
+        }
+    }
+
+    fn add(&mut self, child: Result<Node, NodeError>) -> &mut BlockBuilder {
+        add_child(&mut self.node, &mut self.error, child);
+        self
+    }
+
+    /// Add a header of the given level.
+    pub fn heading<F>(&mut self, level: i32, f: F) -> &mut BlockBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let mut node = Node::new(NodeType::Header);
+        let child = node.set_header_level(level)
+            .and_then(|_| InlineBuilder::fill(node, f));
+        self.add(child)
+    }
+
+    /// Add a paragraph.
+    pub fn paragraph<F>(&mut self, f: F) -> &mut BlockBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let child = InlineBuilder::fill(Node::new(NodeType::Paragraph), f);
+        self.add(child)
+    }
+
+    /// Add a block quote.
+    pub fn block_quote<F>(&mut self, f: F) -> &mut BlockBuilder
+        where F: FnOnce(&mut BlockBuilder) -> &mut BlockBuilder {
+        let mut quote = BlockBuilder::new(Node::new(NodeType::BlockQuote));
+        f(&mut quote);
+        self.add(quote.finish())
+    }
+
+    /// Add a list.
+    pub fn list<F>(&mut self, list_type: ListType, delim: DelimType, start: i32, tight: bool,
+                   f: F) -> &mut BlockBuilder
+        where F: FnOnce(&mut ListBuilder) -> &mut ListBuilder {
+        let child = Node::list(list_type, delim, start, tight).and_then(|node| {
+            let mut list = ListBuilder { node: node, error: None };
+            f(&mut list);
+            match list.error {
+                Some(err) => Err(err),
+                None => Ok(list.node),
+            }
+        });
+        self.add(child)
+    }
+
+    /// Add a code block.
+    pub fn code_block(&mut self, info: &str, literal: &str) -> &mut BlockBuilder {
+        self.add(Node::code_block(info, literal))
+    }
+
+    /// Add a raw HTML block.
+    pub fn html(&mut self, literal: &str) -> &mut BlockBuilder {
+        self.add(literal_node(NodeType::Html, literal))
+    }
+
+    /// Add a horizontal rule.
+    pub fn hrule(&mut self) -> &mut BlockBuilder {
+        self.add(Ok(Node::new(NodeType::Hrule)))
+    }
+
+    /// Add an existing block node.
+    pub fn node(&mut self, node: Node) -> &mut BlockBuilder {
+        self.add(Ok(node))
+    }
+}
+
+/// Fills a list with items.
+pub struct ListBuilder {
+    node: Node,
+    error: Option<NodeError>,
+}
+
+impl ListBuilder {
+    /// Add an item to the list.
+    pub fn item<F>(&mut self, f: F) -> &mut ListBuilder
+        where F: FnOnce(&mut BlockBuilder) -> &mut BlockBuilder {
+        let mut item = BlockBuilder::new(Node::new(NodeType::Item));
+        f(&mut item);
+        add_child(&mut self.node, &mut self.error, item.finish());
+        self
+    }
+}
+
+/// Fills a paragraph, header or inline container with inlines.
+pub struct InlineBuilder {
+    node: Node,
+    error: Option<NodeError>,
+}
+
+impl InlineBuilder {
+    fn fill<F>(node: Node, f: F) -> Result<Node, NodeError>
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let mut inlines = InlineBuilder { node: node, error: None };
+        f(&mut inlines);
+        match inlines.error {
+            Some(err) => Err(err),
+            None => Ok(inlines.node),
+        }
+    }
+
+    fn add(&mut self, child: Result<Node, NodeError>) -> &mut InlineBuilder {
+        add_child(&mut self.node, &mut self.error, child);
+        self
+    }
+
+    /// Add plain text.
+    pub fn text(&mut self, literal: &str) -> &mut InlineBuilder {
+        self.add(Node::text(literal))
+    }
+
+    /// Add an inline code span.
+    pub fn code(&mut self, literal: &str) -> &mut InlineBuilder {
+        self.add(Node::code(literal))
+    }
+
+    /// Add raw inline HTML.
+    pub fn inline_html(&mut self, literal: &str) -> &mut InlineBuilder {
+        self.add(literal_node(NodeType::InlineHtml, literal))
+    }
+
+    /// Add a soft line break.
+    pub fn softbreak(&mut self) -> &mut InlineBuilder {
+        self.add(Ok(Node::new(NodeType::SoftBreak)))
+    }
+
+    /// Add a hard line break.
+    pub fn linebreak(&mut self) -> &mut InlineBuilder {
+        self.add(Ok(Node::new(NodeType::LineBreak)))
+    }
+
+    /// Add emphasized inlines.
+    pub fn emph<F>(&mut self, f: F) -> &mut InlineBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let child = InlineBuilder::fill(Node::new(NodeType::Emph), f);
+        self.add(child)
+    }
+
+    /// Add strongly emphasized inlines.
+    pub fn strong<F>(&mut self, f: F) -> &mut InlineBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let child = InlineBuilder::fill(Node::new(NodeType::Strong), f);
+        self.add(child)
+    }
+
+    /// Add a link whose text is built by `f`.
+    pub fn link<F>(&mut self, url: &str, title: &str, f: F) -> &mut InlineBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let child = link_node(NodeType::Link, url, title, vec![])
+            .and_then(|node| InlineBuilder::fill(node, f));
+        self.add(child)
+    }
+
+    /// Add an image whose alt text is built by `f`.
+    pub fn image<F>(&mut self, url: &str, title: &str, f: F) -> &mut InlineBuilder
+        where F: FnOnce(&mut InlineBuilder) -> &mut InlineBuilder {
+        let child = link_node(NodeType::Image, url, title, vec![])
+            .and_then(|node| InlineBuilder::fill(node, f));
+        self.add(child)
+    }
+
+    /// Add an existing inline node.
+    pub fn node(&mut self, node: Node) -> &mut InlineBuilder {
+        self.add(Ok(node))
+    }
+}
+
+/// Append a freshly built child to `parent`, unless an earlier error has
+/// already been recorded. Only the first error is kept.
+fn add_child(parent: &mut Node, error: &mut Option<NodeError>,
+             child: Result<Node, NodeError>) {
+    if error.is_some() {
+        return;
+    }
+
+    let appended = child.and_then(|child| append_checked(parent, child));
+    if let Err(err) = appended {
+        *error = Some(err);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{Node, NodeType, NodeError};
+
+    #[test]
+    fn test_invalid_nesting() {
+        let result = DocumentBuilder::new()
+            .paragraph(|p| p.node(Node::new(NodeType::Paragraph)))
+            .build();
+        assert_eq!(result.err(), Some(NodeError::InvalidChild {
+            parent: NodeType::Paragraph,
+            child: NodeType::Paragraph,
+        }));
+    }
+
+    #[test]
+    fn test_first_error_reported() {
+        let result = DocumentBuilder::new()
+            .heading(7, |h| h.text("Too deep"))
+            .paragraph(|p| p.text("nul\0"))
+            .build();
+        assert_eq!(result.err(), Some(NodeError::InvalidValue {
+            node_type: NodeType::Header,
+            property: "header_level",
+        }));
+    }
+
+    #[test]
+    fn test_link_constructor() {
+        let result = Node::link("/url", "", vec![Node::new(NodeType::Item)]);
+        assert_eq!(result.err(), Some(NodeError::InvalidChild {
+            parent: NodeType::Link,
+            child: NodeType::Item,
+        }));
+    }
+}
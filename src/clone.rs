@@ -0,0 +1,124 @@
+use {Node, NodeType, Document, EventType, NodeError};
+
+impl Node {
+    /// Create an owned copy of this node and all of its descendants. Every
+    /// type-specific property is copied, but source positions are not:
+    /// libcmark offers no way to set them, so the copied nodes report zero for
+    /// `start_line` and friends.
+    ///
+    /// The copy is not attached to any tree, even if this node is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, render_html, DEFAULT};
+    ///
+    /// let doc = parse_document("# Title\n\nSome *text*", DEFAULT);
+    /// let header = doc.first_child().unwrap().deep_clone();
+    /// let mut copy = doc.deep_clone();
+    /// copy.append_child(header).unwrap();
+    ///
+    /// assert_eq!(render_html(&copy, DEFAULT),
+    ///            "<h1>Title</h1>\n<p>Some <em>text</em></p>\n<h1>Title</h1>\n");
+    /// ```
+    pub fn deep_clone(&self) -> Node {
+        // Walk the subtree iteratively so that deeply nested documents cannot
+        // overflow the stack. `parents` holds the copies of the containers
+        // that are currently open.
+        let mut parents: Vec<Node> = Vec::new();
+        let mut root = None;
+
+        for (event, node) in self.iter() {
+            match event {
+                EventType::Enter => {
+                    let copy = shallow_clone(&node);
+                    if node.node_type().is_leaf() {
+                        attach(&mut parents, &mut root, copy);
+                    }
+                    else {
+                        parents.push(copy);
+                    }
+                }
+                EventType::Exit => {
+                    let copy = parents.pop().unwrap();
+                    attach(&mut parents, &mut root, copy);
+                }
+                _ => (),
+            }
+        }
+
+        root.unwrap()
+    }
+}
+
+impl Clone for Document {
+    /// Create an independent copy of the whole document, as with
+    /// `Node::deep_clone`. The copy has no source positions, so it has no
+    /// `SourceMap` either.
+    fn clone(&self) -> Document {
+        Document::from(self.deep_clone())
+    }
+}
+
+/// Add a finished copy to the innermost open container, or make it the root
+/// of the copy if there is none.
+fn attach(parents: &mut Vec<Node>, root: &mut Option<Node>, copy: Node) {
+    match parents.last_mut() {
+        Some(parent) => {
+            parent.append_child(copy).ok().expect("a copy fits where its original was");
+        }
+        None => *root = Some(copy),
+    }
+}
+
+/// Create a new node with the same type and properties as `node`, but no
+/// children.
+fn shallow_clone(node: &Node) -> Node {
+    let mut copy = Node::new(node.node_type());
+    copy_properties(node, &mut copy)
+        .ok().expect("properties read from a node are valid for its type");
+    copy
+}
+
+fn copy_properties(from: &Node, to: &mut Node) -> Result<(), NodeError> {
+    match from.node_type() {
+        NodeType::Text | NodeType::Code | NodeType::Html | NodeType::InlineHtml => {
+            try!(to.set_literal(from.literal()));
+        }
+        NodeType::CodeBlock => {
+            try!(to.set_literal(from.literal()));
+            try!(to.set_fence_info(from.fence_info()));
+        }
+        NodeType::Header => {
+            try!(to.set_header_level(from.header_level()));
+        }
+        NodeType::List => {
+            try!(to.set_list_type(from.list_type()));
+            try!(to.set_list_delim(from.list_delim()));
+            try!(to.set_list_start(from.list_start()));
+            try!(to.set_list_tight(from.list_tight()));
+        }
+        NodeType::Link | NodeType::Image => {
+            try!(to.set_url(from.url()));
+            try!(to.set_title(from.title()));
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{parse_document, render_xml, DEFAULT};
+
+    #[test]
+    fn test_clone_document() {
+        let doc = parse_document("1) one\n2) [two](/url \"title\")\n\n```rust\nfn main() {}\n```",
+                                 DEFAULT);
+        let copy = doc.clone();
+        drop(doc);
+        let expected = parse_document("1) one\n2) [two](/url \"title\")\n\n```rust\nfn main() {}\n```",
+                                      DEFAULT);
+        assert_eq!(render_xml(&copy, DEFAULT), render_xml(&expected, DEFAULT));
+    }
+}
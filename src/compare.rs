@@ -0,0 +1,249 @@
+use {Node, NodeType, Document, EventType};
+
+use std::hash::{Hash, Hasher};
+
+/// Whether source positions take part in structural comparison and hashing.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Positions {
+    /// Nodes only match if their `start_line`, `start_column`, `end_line` and
+    /// `end_column` are equal.
+    Include,
+    /// Source positions are ignored, so a parsed tree can match one built by
+    /// hand or with `deep_clone`.
+    Ignore,
+}
+
+impl Node {
+    /// Compare the subtrees rooted at this node and `other`. Two subtrees are
+    /// equal if they have the same shape, and each pair of corresponding nodes
+    /// has the same type and type-specific properties.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, Positions, DEFAULT};
+    ///
+    /// let doc = parse_document("Some *text*", DEFAULT);
+    /// let other = parse_document("\nSome _text_", DEFAULT);
+    /// assert!(doc.structural_eq(&other, Positions::Ignore));
+    /// assert!(!doc.structural_eq(&other, Positions::Include));
+    /// ```
+    pub fn structural_eq(&self, other: &Node, positions: Positions) -> bool {
+        let mut left = self.iter();
+        let mut right = other.iter();
+
+        loop {
+            match (left.next(), right.next()) {
+                (None, None) => return true,
+                (Some((left_event, left_node)), Some((right_event, right_node))) => {
+                    if left_event != right_event {
+                        return false;
+                    }
+                    if left_event == EventType::Enter &&
+                       !shallow_eq(&left_node, &right_node, positions) {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+        }
+    }
+
+    /// Compute a hash of the subtree rooted at this node that is consistent
+    /// with `structural_eq`.
+    ///
+    /// The hash is 64-bit FNV-1a over a fixed encoding of the tree, in which
+    /// integers are written little-endian and strings are prefixed with their
+    /// length. It does not depend on the Rust version, the platform or the
+    /// process, so it can be stored and used as a cache key. It may change
+    /// between releases of this crate if the encoding does, which will be
+    /// noted in the changelog.
+    pub fn structural_hash(&self, positions: Positions) -> u64 {
+        let mut hasher = Fnv1a::new();
+        self.hash_structure(&mut hasher, positions);
+        hasher.finish()
+    }
+
+    fn hash_structure<H: Hasher>(&self, hasher: &mut H, positions: Positions) {
+        for (event, node) in self.iter() {
+            if event == EventType::Exit {
+                hasher.write_u8(0);
+                continue;
+            }
+
+            hasher.write_u8(1);
+            write_u32(hasher, node.node_type() as u32);
+            match node.node_type() {
+                NodeType::Text | NodeType::Code | NodeType::Html | NodeType::InlineHtml => {
+                    write_str(hasher, node.literal());
+                }
+                NodeType::CodeBlock => {
+                    write_str(hasher, node.literal());
+                    write_str(hasher, node.fence_info());
+                }
+                NodeType::Header => {
+                    write_u32(hasher, node.header_level() as u32);
+                }
+                NodeType::List => {
+                    write_u32(hasher, node.list_type() as u32);
+                    write_u32(hasher, node.list_delim() as u32);
+                    write_u32(hasher, node.list_start() as u32);
+                    hasher.write_u8(node.list_tight() as u8);
+                }
+                NodeType::Link | NodeType::Image => {
+                    write_str(hasher, node.url());
+                    write_str(hasher, node.title());
+                }
+                _ => (),
+            }
+            if positions == Positions::Include {
+                write_u32(hasher, node.start_line() as u32);
+                write_u32(hasher, node.start_column() as u32);
+                write_u32(hasher, node.end_line() as u32);
+                write_u32(hasher, node.end_column() as u32);
+            }
+        }
+    }
+}
+
+/// Write `value` as four little-endian bytes. `Hasher::write_u32` uses the
+/// platform's byte order, which would make `structural_hash` differ between
+/// platforms.
+fn write_u32<H: Hasher>(hasher: &mut H, value: u32) {
+    hasher.write(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8]);
+}
+
+/// Write the length of `value` followed by its bytes, so that adjacent
+/// strings cannot run into each other.
+fn write_str<H: Hasher>(hasher: &mut H, value: &str) {
+    write_u32(hasher, value.len() as u32);
+    hasher.write(value.as_bytes());
+}
+
+/// The 64-bit FNV-1a hash, used by `structural_hash` because its output is
+/// fixed by its definition rather than by the standard library.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Compare the type, properties and, optionally, the source positions of two
+/// nodes, ignoring their children.
+fn shallow_eq(left: &Node, right: &Node, positions: Positions) -> bool {
+    if left.node_type() != right.node_type() {
+        return false;
+    }
+
+    let properties_eq = match left.node_type() {
+        NodeType::Text | NodeType::Code | NodeType::Html | NodeType::InlineHtml =>
+            left.literal() == right.literal(),
+        NodeType::CodeBlock =>
+            left.literal() == right.literal() &&
+            left.fence_info() == right.fence_info(),
+        NodeType::Header =>
+            left.header_level() == right.header_level(),
+        NodeType::List =>
+            left.list_type() == right.list_type() &&
+            left.list_delim() == right.list_delim() &&
+            left.list_start() == right.list_start() &&
+            left.list_tight() == right.list_tight(),
+        NodeType::Link | NodeType::Image =>
+            left.url() == right.url() &&
+            left.title() == right.title(),
+        _ => true,
+    };
+
+    properties_eq && match positions {
+        Positions::Ignore => true,
+        Positions::Include =>
+            left.start_line() == right.start_line() &&
+            left.start_column() == right.start_column() &&
+            left.end_line() == right.end_line() &&
+            left.end_column() == right.end_column(),
+    }
+}
+
+/// Nodes are equal if the subtrees rooted at them are structurally equal,
+/// ignoring source positions.
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.structural_eq(other, Positions::Ignore)
+    }
+}
+
+impl Eq for Node {}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.hash_structure(hasher, Positions::Ignore);
+    }
+}
+
+/// Documents are equal if their root nodes are.
+impl PartialEq for Document {
+    fn eq(&self, other: &Document) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Document {}
+
+impl Hash for Document {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        (**self).hash(hasher);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, DEFAULT};
+
+    #[test]
+    fn test_clone_is_equal() {
+        let doc = parse_document("# Title\n\n* [link](/url)\n* `code`", DEFAULT);
+        let copy = doc.clone();
+        assert_eq!(doc, copy);
+        assert_eq!(doc.structural_hash(Positions::Ignore),
+                   copy.structural_hash(Positions::Ignore));
+        assert!(!doc.structural_eq(&copy, Positions::Include));
+    }
+
+    #[test]
+    fn test_different_properties() {
+        let doc = parse_document("# Title", DEFAULT);
+        let other = parse_document("## Title", DEFAULT);
+        assert!(doc != other);
+        assert!(doc.structural_hash(Positions::Ignore) !=
+                other.structural_hash(Positions::Ignore));
+    }
+
+    #[test]
+    fn test_node_eq_and_stable_hash() {
+        let doc = parse_document("*a*\n\n*a*", DEFAULT);
+        let first = doc.first_child().unwrap();
+        let second = first.next().unwrap();
+        assert!(*first == *second);
+        assert!(*first != *doc);
+
+        // The hash of an empty document is fixed by the encoding: an enter
+        // marker, the node type as four bytes and an exit marker.
+        let empty = Node::new(NodeType::Document);
+        assert_eq!(empty.structural_hash(Positions::Ignore), 0x07fd7bf1b8fb1567);
+    }
+}
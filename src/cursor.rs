@@ -0,0 +1,314 @@
+use {raw, Node, NodeRef, NodeMut, NodeIterator, EventType, NodeError};
+use util::Binding;
+
+/// A cursor over a tree that can move freely and edit the tree as it goes.
+///
+/// A plain `NodeIterator` is left in an undefined state if the node it is on
+/// is unlinked or replaced. `TreeCursor` wraps the same libcmark iterator, but
+/// every edit repositions it with `cmark_iter_reset`, so the cursor always
+/// stays at a well-defined place:
+///
+/// * `replace` leaves the cursor on the replacement node.
+/// * `remove` moves the cursor to the position just before the removed node,
+///   so that `next` continues with whatever followed it.
+/// * `insert_before` and `insert_after` leave the cursor where it was. Nodes
+///   inserted after the cursor are visited by `next`; nodes inserted before it
+///   are not.
+///
+/// The cursor never leaves the subtree it was created on, and that root can
+/// be neither replaced nor removed.
+///
+/// The handles returned by `node`, `root` and `next` borrow the cursor, so
+/// the compiler ensures they are dropped before the next edit, which might
+/// free the node they point to.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, render_html, TreeCursor, NodeType, DEFAULT};
+///
+/// let mut doc = parse_document("Keep\n\n<div>drop</div>\n\nAlso keep", DEFAULT);
+/// {
+///     let mut cursor = TreeCursor::new(doc.root_mut());
+///     while let Some((_, node)) = cursor.next() {
+///         if node.node_type() == NodeType::Html {
+///             cursor.remove().unwrap();
+///         }
+///     }
+/// }
+/// assert_eq!(render_html(&doc, DEFAULT), "<p>Keep</p>\n<p>Also keep</p>\n");
+/// ```
+///
+/// ```compile_fail
+/// use rcmark::{parse_document, TreeCursor, DEFAULT};
+///
+/// let mut doc = parse_document("Gone", DEFAULT);
+/// let mut cursor = TreeCursor::new(doc.root_mut());
+/// cursor.goto_first_child();
+/// let node = cursor.node();
+/// cursor.remove().unwrap();
+/// node.node_type();
+/// ```
+pub struct TreeCursor<'a> {
+    iter: NodeIterator<'a>,
+}
+
+impl<'a> TreeCursor<'a> {
+    /// Create a cursor positioned on the `Enter` event of `root`. The first
+    /// call to `next` moves to the node after the root.
+    pub fn new(root: NodeMut<'a>) -> TreeCursor<'a> {
+        // `root` borrows the tree exclusively for `'a`, and the cursor takes
+        // that borrow over.
+        let mut iter = unsafe { NodeIterator::from_raw(raw::cmark_iter_new(root.raw())) };
+        let root = iter.root();
+        iter.reset(&root, EventType::Enter);
+        TreeCursor {
+            iter: iter,
+        }
+    }
+
+    /// Get the node the cursor is on.
+    pub fn node(&self) -> NodeRef {
+        self.iter.node()
+    }
+
+    /// Get whether the cursor is entering or exiting its node. Nodes that
+    /// cannot have children are only ever entered.
+    pub fn event_type(&self) -> EventType {
+        self.iter.event_type()
+    }
+
+    /// Get the root of the subtree this cursor walks.
+    pub fn root(&self) -> NodeRef {
+        self.iter.root()
+    }
+
+    /// Advance to the next event in document order, as `NodeIterator` does.
+    ///
+    /// `TreeCursor` is not an `Iterator`, since the node returned here
+    /// borrows the cursor and so must be dropped before the cursor is used to
+    /// edit the tree.
+    pub fn next<'c>(&'c mut self) -> Option<(EventType, NodeRef<'c>)> {
+        self.iter.next()
+    }
+
+    fn is_root(&self, node: &Node) -> bool {
+        node.raw() == self.iter.root().raw()
+    }
+
+    fn goto(&mut self, target: Option<NodeRef<'a>>) -> bool {
+        match target {
+            Some(node) => {
+                self.iter.reset(&node, EventType::Enter);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Move to the parent of the current node. Returns `false`, leaving the
+    /// cursor where it is, if the cursor is on the root.
+    pub fn goto_parent(&mut self) -> bool {
+        let node = self.iter.node();
+        let target = if self.is_root(&node) { None } else { node.parent() };
+        self.goto(target)
+    }
+
+    /// Move to the first child of the current node, if it has one.
+    pub fn goto_first_child(&mut self) -> bool {
+        let target = self.iter.node().first_child();
+        self.goto(target)
+    }
+
+    /// Move to the last child of the current node, if it has one.
+    pub fn goto_last_child(&mut self) -> bool {
+        let target = self.iter.node().last_child();
+        self.goto(target)
+    }
+
+    /// Move to the next sibling of the current node, if it has one.
+    pub fn goto_next_sibling(&mut self) -> bool {
+        let node = self.iter.node();
+        let target = if self.is_root(&node) { None } else { node.next() };
+        self.goto(target)
+    }
+
+    /// Move to the previous sibling of the current node, if it has one.
+    pub fn goto_previous_sibling(&mut self) -> bool {
+        let node = self.iter.node();
+        let target = if self.is_root(&node) { None } else { node.previous() };
+        self.goto(target)
+    }
+
+    /// Arrange for the next call to `next` to move past the children of the
+    /// current node rather than into them. The current node's `Exit` event is
+    /// skipped along with its children.
+    pub fn skip_children(&mut self) {
+        let node = self.iter.node();
+        if self.event_type() == EventType::Enter && !node.node_type().is_leaf() {
+            self.iter.reset(&node, EventType::Exit);
+        }
+    }
+
+    /// Replace the current node with `replacement`, returning the detached
+    /// original. The cursor moves to the `Enter` event of the replacement.
+    pub fn replace(&mut self, replacement: Node) -> Result<Node, NodeError> {
+        let mut node = try!(self.parented_node());
+        let replacement = try!(insert(&mut node, replacement, Side::Before));
+
+        self.iter.reset(&replacement, EventType::Enter);
+        node.detach()
+    }
+
+    /// Remove the current node from the tree, returning it. The cursor moves
+    /// to the position just before the removed node: the previous sibling if
+    /// there is one, otherwise the `Enter` event of the parent.
+    pub fn remove(&mut self) -> Result<Node, NodeError> {
+        let node = try!(self.parented_node());
+        let current = self.iter.node();
+
+        match current.previous() {
+            Some(previous) => {
+                let event = if previous.node_type().is_leaf() {
+                    EventType::Enter
+                }
+                else {
+                    EventType::Exit
+                };
+                self.iter.reset(&previous, event);
+            }
+            None => {
+                let parent = current.parent().unwrap();
+                self.iter.reset(&parent, EventType::Enter);
+            }
+        }
+
+        node.detach()
+    }
+
+    /// Insert `sibling` immediately before the current node.
+    pub fn insert_before(&mut self, sibling: Node) -> Result<(), NodeError> {
+        let mut node = try!(self.parented_node());
+        let event = self.event_type();
+        try!(insert(&mut node, sibling, Side::Before));
+        self.iter.reset(&self.iter.node(), event);
+        Ok(())
+    }
+
+    /// Insert `sibling` immediately after the current node.
+    pub fn insert_after(&mut self, sibling: Node) -> Result<(), NodeError> {
+        let mut node = try!(self.parented_node());
+        let event = self.event_type();
+        try!(insert(&mut node, sibling, Side::After));
+        self.iter.reset(&self.iter.node(), event);
+        Ok(())
+    }
+
+    /// Get the current node for editing, checking that it is not the root and
+    /// so can gain siblings or be removed.
+    fn parented_node(&self) -> Result<NodeMut<'a>, NodeError> {
+        let node = self.iter.node();
+        if self.is_root(&node) || node.parent().is_none() {
+            Err(NodeError::NoParent {
+                node_type: node.node_type(),
+            })
+        }
+        else {
+            // The cursor holds the only borrow of the tree, and edits through
+            // this handle never outlive the method that made it.
+            Ok(unsafe { NodeMut::from_raw(node.raw()) })
+        }
+    }
+}
+
+enum Side {
+    Before,
+    After,
+}
+
+/// Insert `sibling` next to `node`, returning a handle to it in its new place.
+fn insert<'a>(node: &mut NodeMut<'a>, sibling: Node, side: Side) -> Result<NodeRef<'a>, NodeError> {
+    let parent_type = node.parent().unwrap().node_type();
+    let sibling_type = sibling.node_type();
+    if parent_type.can_contain(sibling_type) {
+        let raw = sibling.raw();
+        let inserted = match side {
+            Side::Before => node.insert_before(sibling),
+            Side::After => node.insert_after(sibling),
+        };
+        if inserted.is_ok() {
+            return Ok(unsafe { NodeRef::from_raw(raw) });
+        }
+    }
+    Err(NodeError::InvalidChild {
+        parent: parent_type,
+        child: sibling_type,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, render_html, Node, NodeType, NodeError, DEFAULT};
+
+    #[test]
+    fn test_navigation() {
+        let mut doc = parse_document("# A\n\n*b* c", DEFAULT);
+        let mut cursor = TreeCursor::new(doc.root_mut());
+        assert!(!cursor.goto_parent());
+        assert!(!cursor.goto_next_sibling());
+        assert!(cursor.goto_last_child());
+        assert_eq!(cursor.node().node_type(), NodeType::Paragraph);
+        assert!(cursor.goto_first_child());
+        assert!(cursor.goto_next_sibling());
+        assert_eq!(cursor.node().literal(), " c");
+        assert!(!cursor.goto_next_sibling());
+        assert!(cursor.goto_parent());
+        assert!(cursor.goto_previous_sibling());
+        assert_eq!(cursor.node().node_type(), NodeType::Header);
+    }
+
+    #[test]
+    fn test_replace_and_skip() {
+        let mut doc = parse_document("*a* b", DEFAULT);
+        {
+            let mut cursor = TreeCursor::new(doc.root_mut());
+            let mut seen = Vec::new();
+            while let Some((event, node)) = cursor.next() {
+                if event == EventType::Exit {
+                    continue;
+                }
+                seen.push(node.node_type());
+                if node.node_type() == NodeType::Emph {
+                    let old = cursor.replace(Node::new(NodeType::Strong)).unwrap();
+                    assert_eq!(old.first_child().unwrap().literal(), "a");
+                    cursor.skip_children();
+                }
+            }
+            assert_eq!(seen, vec![NodeType::Paragraph, NodeType::Emph, NodeType::Text]);
+        }
+        assert_eq!(render_html(&doc, DEFAULT), "<p><strong></strong> b</p>\n");
+    }
+
+    #[test]
+    fn test_insert_and_errors() {
+        let mut doc = parse_document("a", DEFAULT);
+        let mut cursor = TreeCursor::new(doc.root_mut());
+        assert_eq!(cursor.remove().err(),
+                   Some(NodeError::NoParent { node_type: NodeType::Document }));
+        cursor.goto_first_child();
+        assert_eq!(cursor.insert_after(Node::new(NodeType::Text)).err(),
+                   Some(NodeError::InvalidChild { parent: NodeType::Document,
+                                                  child: NodeType::Text }));
+        cursor.insert_after(Node::new(NodeType::Hrule)).unwrap();
+        cursor.insert_before(Node::new(NodeType::Hrule)).unwrap();
+        assert_eq!(cursor.node().node_type(), NodeType::Paragraph);
+
+        let mut rest = Vec::new();
+        while let Some((_, node)) = cursor.next() {
+            rest.push(node.node_type());
+        }
+        assert_eq!(rest, vec![NodeType::Text, NodeType::Paragraph, NodeType::Hrule,
+                              NodeType::Document]);
+    }
+}
@@ -0,0 +1,166 @@
+use {raw, Node, NodeMut};
+use util::Binding;
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::ptr;
+
+use libc;
+
+/// The values attached to a single node, keyed by their type. A boxed map is
+/// stored in the node's libcmark user data pointer the first time a value is
+/// attached. Values must be `Send` so that a `Document` carrying them can be
+/// moved to another thread.
+pub type DataMap = HashMap<TypeId, Box<dyn Any + Send>>;
+
+/// Methods for attaching values, shared by `Node` and `NodeMut` in the same
+/// way as `node_mutators!`.
+macro_rules! data_mutators {
+    () => {
+        /// Attach `value` to this node, returning the value of the same type
+        /// that was previously attached, if any. A node can hold one value of
+        /// each type.
+        ///
+        /// Attached values stay with the node when it is moved within or
+        /// between trees, and are dropped when the tree that owns the node is
+        /// freed. They are not copied by `deep_clone`.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use rcmark::{parse_document, DEFAULT};
+        ///
+        /// struct Anchor(String);
+        ///
+        /// let mut doc = parse_document("# Getting Started", DEFAULT);
+        /// doc.first_child_mut().unwrap().set_data(Anchor("getting-started".to_string()));
+        ///
+        /// let header = doc.first_child().unwrap();
+        /// assert_eq!(header.data::<Anchor>().unwrap().0, "getting-started");
+        /// assert!(header.data::<u32>().is_none());
+        /// ```
+        pub fn set_data<T: Any + Send>(&mut self, value: T) -> Option<T> {
+            let map = unsafe { data_map_mut(self.raw()) };
+            map.insert(TypeId::of::<T>(), Box::new(value)).map(unbox)
+        }
+
+        /// Get a mutable reference to the value of type `T` attached to this
+        /// node.
+        pub fn data_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+            let map = unsafe { data_map_mut(self.raw()) };
+            map.get_mut(&TypeId::of::<T>()).and_then(|value| value.downcast_mut::<T>())
+        }
+
+        /// Detach and return the value of type `T` attached to this node.
+        pub fn take_data<T: Any + Send>(&mut self) -> Option<T> {
+            let map = unsafe { data_map_mut(self.raw()) };
+            map.remove(&TypeId::of::<T>()).map(unbox)
+        }
+    }
+}
+
+impl Node {
+    data_mutators!();
+
+    /// Get the value of type `T` attached to this node.
+    ///
+    /// The value can only be replaced or taken through `&mut Node` or a
+    /// `NodeMut`, both of which need an exclusive borrow of the tree's owner,
+    /// so it cannot be freed while the returned reference is in use:
+    ///
+    /// ```compile_fail
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let mut doc = parse_document("# Title", DEFAULT);
+    /// doc.first_child_mut().unwrap().set_data(1u32);
+    ///
+    /// let header = doc.first_child().unwrap();
+    /// let value = header.data::<u32>().unwrap();
+    /// doc.first_child_mut().unwrap().take_data::<u32>();
+    /// assert_eq!(*value, 1);
+    /// ```
+    pub fn data<T: Any + Send>(&self) -> Option<&T> {
+        self.data_map()
+            .and_then(|map| map.get(&TypeId::of::<T>()))
+            .and_then(|value| value.downcast_ref::<T>())
+    }
+
+    fn data_map(&self) -> Option<&DataMap> {
+        unsafe {
+            let map = raw::cmark_node_get_user_data(self.raw()) as *const DataMap;
+            if map.is_null() { None } else { Some(&*map) }
+        }
+    }
+}
+
+impl<'a> NodeMut<'a> {
+    data_mutators!();
+}
+
+/// Get the map of values attached to `node`, creating it if there is none.
+/// The caller picks the lifetime, and must hold exclusive access to the node
+/// for as long as the map is used.
+unsafe fn data_map_mut<'n>(node: *mut raw::cmark_node) -> &'n mut DataMap {
+    let mut map = raw::cmark_node_get_user_data(node) as *mut DataMap;
+    if map.is_null() {
+        map = Box::into_raw(Box::new(DataMap::new()));
+        raw::cmark_node_set_user_data(node, map as *const libc::c_void);
+    }
+    &mut *map
+}
+
+fn unbox<T: Any + Send>(value: Box<dyn Any + Send>) -> T {
+    *value.downcast::<T>().ok().expect("attached values are keyed by their type")
+}
+
+/// Drop the values attached to `root` and all of its descendants. Called just
+/// before libcmark frees the subtree, since libcmark itself knows nothing
+/// about the user data pointer's contents.
+pub fn free_subtree(root: &Node) {
+    for (_, node) in root.iter() {
+        unsafe {
+            let map = raw::cmark_node_get_user_data(node.raw()) as *mut DataMap;
+            if !map.is_null() {
+                raw::cmark_node_set_user_data(node.raw(), ptr::null());
+                drop(Box::from_raw(map));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Node, NodeType};
+
+    use std::sync::Arc;
+
+    #[test]
+    fn test_data_dropped_with_tree() {
+        let marker = Arc::new(());
+        {
+            let mut paragraph = Node::new(NodeType::Paragraph);
+            let mut text = Node::new(NodeType::Text);
+            text.set_data(marker.clone());
+            paragraph.append_child(text).unwrap();
+
+            paragraph.append_child(Node::new(NodeType::Emph)).unwrap();
+            let text = paragraph.first_child_mut().unwrap().detach().unwrap();
+            paragraph.first_child_mut().unwrap().append_child(text).unwrap();
+
+            assert_eq!(Arc::strong_count(&marker), 2);
+            let moved = paragraph.first_child().unwrap().first_child().unwrap();
+            assert!(moved.data::<Arc<()>>().is_some());
+        }
+        assert_eq!(Arc::strong_count(&marker), 1);
+    }
+
+    #[test]
+    fn test_replace_and_take() {
+        let mut node = Node::new(NodeType::Text);
+        assert_eq!(node.set_data(1u32), None);
+        assert_eq!(node.set_data(2u32), Some(1));
+        *node.data_mut::<u32>().unwrap() += 1;
+        assert_eq!(node.take_data::<u32>(), Some(3));
+        assert_eq!(node.data::<u32>(), None);
+    }
+}
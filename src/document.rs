@@ -0,0 +1,182 @@
+use {raw, Node, NodeRef, NodeMut, NodeType, SourceMap};
+use data;
+use util::Binding;
+
+use std::fmt;
+use std::ops::Deref;
+
+/// An owned CommonMark document. The document owns its root node and every
+/// node beneath it, and frees them all when dropped. Nodes inside the
+/// document are reached through `NodeRef` handles for reading and `NodeMut`
+/// handles for editing, which borrow from the document and so cannot outlive
+/// it.
+///
+/// A `Document` dereferences to its root `Node`, so it can be passed directly
+/// to the `render_*` functions. It only does so for reading: edits go through
+/// `root_mut` and the other `_mut` methods, so the root itself can never be
+/// swapped out from under the document.
+///
+/// Documents produced by `Parser` and `parse_document` also keep a
+/// `SourceMap` of the parsed text, for mapping node positions back to it.
+///
+/// # Threads
+///
+/// libcmark keeps no global state while parsing or rendering, so a tree may
+/// be used from any thread as long as only one thread touches it at a time.
+/// `Document` is therefore `Send`: a document parsed on one thread can be
+/// handed to another to be rendered. It is not `Sync`, since even libcmark's
+/// getters may modify a node, and the `NodeRef` handles borrowed from a
+/// document are neither, so they cannot escape the thread that owns it.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{Document, NodeType};
+///
+/// let doc = Document::new();
+/// assert_eq!(doc.root().node_type(), NodeType::Document);
+/// ```
+///
+/// Handles into a document cannot be sent to another thread:
+///
+/// ```compile_fail
+/// fn is_send<T: Send>(_: T) {}
+///
+/// let doc = rcmark::Document::new();
+/// is_send(doc.root());
+/// ```
+///
+/// Its root can only be edited, never replaced:
+///
+/// ```compile_fail
+/// use rcmark::{Document, Node, NodeType};
+///
+/// let mut doc = Document::new();
+/// *doc = Node::new(NodeType::Paragraph);
+/// ```
+///
+/// Nor can a document be shared between threads:
+///
+/// ```compile_fail
+/// fn is_sync<T: Sync>() {}
+///
+/// is_sync::<rcmark::Document>();
+/// ```
+pub struct Document {
+    root: Node,
+    source_map: Option<SourceMap>,
+}
+
+impl Document {
+    /// Take ownership of a raw root `cmark_node`, such as the one returned by
+    /// `cmark_parse_document`.
+    pub unsafe fn from_raw(raw: *mut raw::cmark_node) -> Document {
+        Document {
+            root: Node::from_raw(raw, true),
+            source_map: None,
+        }
+    }
+
+    /// Create a new, empty document.
+    pub fn new() -> Document {
+        Document {
+            root: Node::new(NodeType::Document),
+            source_map: None,
+        }
+    }
+
+    /// Get a handle to the root node of this document.
+    pub fn root(&self) -> NodeRef {
+        unsafe {
+            NodeRef::from_raw(self.root.raw())
+        }
+    }
+
+    /// Get a handle for editing the root node of this document and, through
+    /// it, the rest of the tree.
+    pub fn root_mut(&mut self) -> NodeMut {
+        unsafe {
+            NodeMut::from_raw(self.root.raw())
+        }
+    }
+
+    /// Get a handle for editing the first child of the root node, or `None`
+    /// if the document is empty.
+    pub fn first_child_mut(&mut self) -> Option<NodeMut> {
+        self.root_mut().into_first_child()
+    }
+
+    /// Get a handle for editing the last child of the root node, or `None`
+    /// if the document is empty.
+    pub fn last_child_mut(&mut self) -> Option<NodeMut> {
+        self.root_mut().into_last_child()
+    }
+
+    /// Get the map of the text this document was parsed from, or `None` if it
+    /// was not produced by the parser.
+    pub fn source_map(&self) -> Option<&SourceMap> {
+        self.source_map.as_ref()
+    }
+
+    /// Attach the map of the text this document was parsed from.
+    pub fn set_source_map(&mut self, source_map: Option<SourceMap>) {
+        self.source_map = source_map;
+    }
+
+    /// Give up the document wrapper, returning the owned root node.
+    pub fn into_node(self) -> Node {
+        self.root
+    }
+}
+
+// Moving a document to another thread is sound as long as nothing left on
+// the old thread can still reach its tree. That holds because:
+//
+// * The document owns every node in its tree, and libcmark nodes hold no
+//   thread-local or global state.
+// * The document's root is the only `Node` value that refers into the tree.
+//   A safe `Node` always owns its node: `append_child` and friends take the
+//   child by value and give up the handle once libcmark has accepted it, and
+//   only the unsafe `Node::from_raw` can make a `Node` that does not own its
+//   node. The root cannot be swapped for another `Node` either, since
+//   `Document` only dereferences to it immutably.
+// * Every other safe handle into the tree, whether `&Node`, `NodeRef`,
+//   `NodeMut`, `NodeIterator` or `TreeCursor`, borrows the document, so the
+//   document cannot be moved while one exists. None of them is `Send` either.
+// * Every field is `Send`, including the values attached with `set_data`,
+//   which `DataMap` requires to be `Send`. `assert_fields_send` checks this.
+unsafe impl Send for Document {}
+
+#[allow(dead_code)]
+fn assert_fields_send() {
+    fn is_send<T: Send>() {}
+    is_send::<Option<SourceMap>>();
+    is_send::<data::DataMap>();
+}
+
+impl From<Node> for Document {
+    /// Use an owned node as the root of a document.
+    fn from(root: Node) -> Document {
+        Document {
+            root: root,
+            source_map: None,
+        }
+    }
+}
+
+impl Deref for Document {
+    type Target = Node;
+
+    fn deref(&self) -> &Node { &self.root }
+}
+
+impl fmt::Debug for Document {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if fmt.alternate() {
+            fmt::Debug::fmt(&self.root, fmt)
+        }
+        else {
+            write!(fmt, "Document[root = {:?}]", self.root)
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use {Node, NodeType, EventType};
+
+use std::fmt::{self, Write};
+
+impl Node {
+    /// Render the subtree rooted at this node as an indented outline, one node
+    /// per line. Each line shows the node type, its type-specific properties
+    /// and, where libcmark recorded one, its source range. This is the same
+    /// output as the alternate `{:#?}` debug format.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let doc = parse_document("# Hello\n\n[link](/url)", DEFAULT);
+    /// let dump = doc.dump();
+    /// assert!(dump.starts_with("document"));
+    /// assert!(dump.contains("\n  header level=1 [1:1-1:7]\n    text \"Hello\"\n"));
+    /// assert!(dump.contains("\n    link url=\"/url\" title=\"\"\n      text \"link\"\n"));
+    /// ```
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        write_tree(self, &mut out).unwrap();
+        out
+    }
+}
+
+/// Write the outline produced by `Node::dump` to `out`.
+pub fn write_tree<W: Write>(root: &Node, out: &mut W) -> fmt::Result {
+    let mut depth = 0;
+
+    for (event, node) in root.iter() {
+        if event == EventType::Exit {
+            depth -= 1;
+            continue;
+        }
+
+        for _ in 0..depth {
+            try!(out.write_str("  "));
+        }
+        try!(out.write_str(node.type_string()));
+
+        match node.node_type() {
+            NodeType::Text | NodeType::Code | NodeType::Html | NodeType::InlineHtml => {
+                try!(write!(out, " {:?}", node.literal()));
+            }
+            NodeType::CodeBlock => {
+                try!(write!(out, " info={:?} {:?}", node.fence_info(), node.literal()));
+            }
+            NodeType::Header => {
+                try!(write!(out, " level={}", node.header_level()));
+            }
+            NodeType::List => {
+                try!(write!(out, " type={:?} delim={:?} start={} tight={}",
+                            node.list_type(), node.list_delim(),
+                            node.list_start(), node.list_tight()));
+            }
+            NodeType::Link | NodeType::Image => {
+                try!(write!(out, " url={:?} title={:?}", node.url(), node.title()));
+            }
+            _ => (),
+        }
+
+        // Inline nodes do not record positions, so leave them out rather than
+        // printing a meaningless range of zeroes.
+        if node.start_line() != 0 {
+            try!(write!(out, " [{}:{}-{}:{}]",
+                        node.start_line(), node.start_column(),
+                        node.end_line(), node.end_column()));
+        }
+        try!(out.write_str("\n"));
+
+        if !node.node_type().is_leaf() {
+            depth += 1;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,299 @@
+use {Node, NodeRef, NodeMut, NodeType, NodeError};
+use util::Binding;
+
+impl<'a> NodeMut<'a> {
+    /// Put `other` where this node is in its tree, and detach this node.
+    ///
+    /// The returned `Node` owns the detached node and its children, so it is
+    /// freed when the result is dropped. The handle is consumed, so it cannot
+    /// be used to reach the detached node:
+    ///
+    /// ```compile_fail
+    /// use rcmark::{parse_document, Node, NodeType, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Old", DEFAULT);
+    /// let mut para = doc.first_child_mut().unwrap();
+    /// drop(para.replace_with(Node::new(NodeType::Hrule)));
+    /// para.node_type();
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, render_html, Node, NodeType, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Old\n\nKept", DEFAULT);
+    /// let old = doc.first_child_mut().unwrap().replace_with(Node::new(NodeType::Hrule)).unwrap();
+    /// assert_eq!(old.node_type(), NodeType::Paragraph);
+    /// assert_eq!(render_html(&doc, DEFAULT), "<hr />\n<p>Kept</p>\n");
+    /// ```
+    pub fn replace_with(mut self, other: Node) -> Result<Node, NodeError> {
+        let parent = try!(parent_type(&self));
+        try!(check_child(parent, other.node_type()));
+        if let Err(other) = self.insert_before(other) {
+            return Err(invalid_child(parent, other.node_type()));
+        }
+        self.detach()
+    }
+
+    /// Put `wrapper` where this node is in its tree, and move this node
+    /// inside it as its last child.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, render_html, Node, NodeType, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Quote me", DEFAULT);
+    /// doc.first_child_mut().unwrap().wrap_in(Node::new(NodeType::BlockQuote)).unwrap();
+    /// assert_eq!(render_html(&doc, DEFAULT),
+    ///            "<blockquote>\n<p>Quote me</p>\n</blockquote>\n");
+    /// ```
+    pub fn wrap_in(&mut self, wrapper: Node) -> Result<(), NodeError> {
+        let parent = try!(parent_type(self));
+        try!(check_child(parent, wrapper.node_type()));
+        try!(check_child(wrapper.node_type(), self.node_type()));
+        let raw = wrapper.raw();
+        if let Err(wrapper) = self.insert_before(wrapper) {
+            return Err(invalid_child(parent, wrapper.node_type()));
+        }
+        // The wrapper is now in the tree, just before this node.
+        let mut wrapper = unsafe { NodeMut::from_raw(raw) };
+        let node = unsafe { Node::from_raw(self.raw(), false) };
+        if wrapper.append_child(node).is_err() {
+            return Err(invalid_child(wrapper.node_type(), self.node_type()));
+        }
+        Ok(())
+    }
+
+    /// Move the children of this node into its parent, where this node was,
+    /// and detach this now empty node.
+    ///
+    /// As with `replace_with`, the returned `Node` owns the detached node and
+    /// the handle is consumed. Nothing is moved if any child could not be
+    /// placed in the parent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, render_html, NodeType, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Some *emphasis*", DEFAULT);
+    /// let emph = doc.first_child_mut().unwrap().into_last_child().unwrap();
+    /// let removed = emph.unwrap().unwrap();
+    /// assert_eq!(removed.node_type(), NodeType::Emph);
+    /// assert_eq!(render_html(&doc, DEFAULT), "<p>Some emphasis</p>\n");
+    /// ```
+    pub fn unwrap(mut self) -> Result<Node, NodeError> {
+        let parent = try!(parent_type(&self));
+        for child in self.children() {
+            try!(check_child(parent, child.node_type()));
+        }
+        for child in self.take_children() {
+            if let Err(child) = self.insert_before(child) {
+                return Err(invalid_child(parent, child.node_type()));
+            }
+        }
+        self.detach()
+    }
+
+    /// Split this text node in two at the byte `offset` into its literal.
+    /// This node keeps the text before the offset, and a new text node holding
+    /// the rest is inserted after it and returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Hello world", DEFAULT);
+    /// let mut text = doc.first_child_mut().unwrap().into_first_child().unwrap();
+    /// assert_eq!(text.split_text_at(5).unwrap().literal(), " world");
+    /// assert_eq!(text.literal(), "Hello");
+    /// ```
+    pub fn split_text_at(&mut self, offset: usize) -> Result<NodeRef, NodeError> {
+        try!(expect_text(self));
+        let parent = try!(parent_type(self));
+        let (head, tail) = {
+            let literal = self.literal();
+            if !literal.is_char_boundary(offset) {
+                return Err(NodeError::InvalidOffset {
+                    offset: offset,
+                    len: literal.len(),
+                });
+            }
+            (literal[..offset].to_string(), literal[offset..].to_string())
+        };
+
+        let rest = try!(Node::text(&tail));
+        let raw = rest.raw();
+        if self.insert_after(rest).is_err() {
+            return Err(invalid_child(parent, NodeType::Text));
+        }
+        try!(self.set_literal(&head));
+        Ok(unsafe { NodeRef::from_raw(raw) })
+    }
+
+    /// Merge the text nodes immediately before and after this text node into
+    /// it, removing them from the tree. Returns the handle along with the
+    /// number of nodes merged.
+    ///
+    /// Unlike `consolidate_text_nodes`, which works on a whole subtree, this
+    /// only touches the run of text nodes around this one.
+    ///
+    /// The merged siblings are freed. This takes the handle by value so that
+    /// it cannot be used on the node a `Transform` is given, since a
+    /// `Pipeline` may be about to visit that node's siblings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Hello world", DEFAULT);
+    /// let mut text = doc.first_child_mut().unwrap().into_first_child().unwrap();
+    /// text.split_text_at(5).unwrap();
+    /// let (text, merged) = text.merge_adjacent_text().unwrap();
+    /// assert_eq!((text.literal(), merged), ("Hello world", 1));
+    /// ```
+    ///
+    /// No other handle to the merged siblings can be alive at that point:
+    ///
+    /// ```compile_fail
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let mut doc = parse_document("Hello world", DEFAULT);
+    /// doc.first_child_mut().unwrap().into_first_child().unwrap().split_text_at(5).unwrap();
+    /// let world = doc.first_child().unwrap().last_child().unwrap();
+    /// let hello = doc.first_child_mut().unwrap().into_first_child().unwrap();
+    /// hello.merge_adjacent_text().unwrap();
+    /// world.literal();
+    /// ```
+    pub fn merge_adjacent_text(mut self) -> Result<(NodeMut<'a>, usize), NodeError> {
+        try!(expect_text(&self));
+        let mut before = Vec::new();
+        let mut after = String::new();
+        let mut merged = 0;
+
+        while let Some(mut node) = adjacent_text(self.previous()) {
+            before.push(node.literal().to_string());
+            node.unlink();
+            merged += 1;
+        }
+        while let Some(mut node) = adjacent_text(self.next()) {
+            after.push_str(node.literal());
+            node.unlink();
+            merged += 1;
+        }
+
+        if merged > 0 {
+            let mut literal: String = before.into_iter().rev().collect();
+            literal.push_str(self.literal());
+            literal.push_str(&after);
+            try!(self.set_literal(&literal));
+        }
+        Ok((self, merged))
+    }
+
+    /// Detach all of the children of this node, returning them in order.
+    pub fn take_children(&mut self) -> Vec<Node> {
+        let mut children = Vec::new();
+        while let Some(raw) = self.first_child().map(|child| child.raw()) {
+            let mut child = unsafe { Node::from_raw(raw, true) };
+            child.unlink();
+            children.push(child);
+        }
+        children
+    }
+}
+
+fn parent_type(node: &Node) -> Result<NodeType, NodeError> {
+    node.parent().map(|parent| parent.node_type()).ok_or(NodeError::NoParent {
+        node_type: node.node_type(),
+    })
+}
+
+fn invalid_child(parent: NodeType, child: NodeType) -> NodeError {
+    NodeError::InvalidChild {
+        parent: parent,
+        child: child,
+    }
+}
+
+fn check_child(parent: NodeType, child: NodeType) -> Result<(), NodeError> {
+    if parent.can_contain(child) { Ok(()) } else { Err(invalid_child(parent, child)) }
+}
+
+fn expect_text(node: &Node) -> Result<(), NodeError> {
+    match node.node_type() {
+        NodeType::Text => Ok(()),
+        found => Err(NodeError::UnexpectedType {
+            expected: NodeType::Text,
+            found: found,
+        }),
+    }
+}
+
+/// Take ownership of `node` if it is a text node, so that it is freed once
+/// unlinked and dropped.
+fn adjacent_text(node: Option<NodeRef>) -> Option<Node> {
+    match node {
+        Some(ref node) if node.node_type() == NodeType::Text =>
+            Some(unsafe { Node::from_raw(node.raw(), true) }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{parse_document, render_html, Node, NodeType, NodeError, DEFAULT};
+
+    #[test]
+    fn test_invalid_nesting() {
+        let mut doc = parse_document("para", DEFAULT);
+        assert_eq!(doc.first_child_mut().unwrap().replace_with(Node::new(NodeType::Item)).err(),
+                   Some(NodeError::InvalidChild { parent: NodeType::Document,
+                                                  child: NodeType::Item }));
+        assert_eq!(doc.first_child_mut().unwrap().wrap_in(Node::new(NodeType::Emph)).err(),
+                   Some(NodeError::InvalidChild { parent: NodeType::Document,
+                                                  child: NodeType::Emph }));
+        assert_eq!(doc.first_child_mut().unwrap().unwrap().err(),
+                   Some(NodeError::InvalidChild { parent: NodeType::Document,
+                                                  child: NodeType::Text }));
+        assert_eq!(doc.root_mut().unwrap().err(),
+                   Some(NodeError::NoParent { node_type: NodeType::Document }));
+        assert_eq!(render_html(&doc, DEFAULT), "<p>para</p>\n");
+    }
+
+    #[test]
+    fn test_split_and_merge() {
+        let mut doc = parse_document("*héllo*", DEFAULT);
+        {
+            let mut emph = doc.first_child_mut().unwrap().into_first_child().unwrap();
+            {
+                let mut text = emph.first_child_mut().unwrap();
+                assert_eq!(text.split_text_at(2).err(),
+                           Some(NodeError::InvalidOffset { offset: 2, len: 6 }));
+                text.split_text_at(3).unwrap();
+                let mut middle = text.into_next().unwrap();
+                middle.split_text_at(1).unwrap();
+                assert_eq!(middle.parent().unwrap().children().count(), 3);
+
+                assert_eq!(middle.literal(), "l");
+                let (middle, merged) = middle.merge_adjacent_text().unwrap();
+                assert_eq!(merged, 2);
+                assert_eq!(middle.literal(), "héllo");
+            }
+            assert_eq!(emph.children().count(), 1);
+
+            assert_eq!(emph.split_text_at(0).err(),
+                       Some(NodeError::UnexpectedType { expected: NodeType::Text,
+                                                        found: NodeType::Emph }));
+            let children = emph.take_children();
+            assert_eq!(children.len(), 1);
+            assert_eq!(children[0].literal(), "héllo");
+            assert!(emph.first_child().is_none());
+        }
+        assert_eq!(render_html(&doc, DEFAULT), "<p><em></em></p>\n");
+    }
+}
@@ -0,0 +1,97 @@
+use NodeType;
+
+use std::error::Error;
+use std::fmt;
+
+/// Errors produced when reading or updating the properties of a node.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum NodeError {
+    /// The property does not apply to nodes of this type, such as asking a
+    /// paragraph for its header level.
+    InvalidProperty {
+        node_type: NodeType,
+        property: &'static str,
+    },
+    /// The property applies to this node type, but libcmark rejected the new
+    /// value, such as a header level outside of 1 through 6.
+    InvalidValue {
+        node_type: NodeType,
+        property: &'static str,
+    },
+    /// A string value contained a NUL byte at `position`, which libcmark
+    /// cannot represent.
+    InteriorNul {
+        node_type: NodeType,
+        property: &'static str,
+        position: usize,
+    },
+    /// A node of type `child` cannot be placed inside a node of type
+    /// `parent`, such as a list item outside of a list.
+    InvalidChild {
+        parent: NodeType,
+        child: NodeType,
+    },
+    /// The operation needs a node with a parent, but the node is the root of
+    /// its tree.
+    NoParent {
+        node_type: NodeType,
+    },
+    /// The operation only applies to nodes of type `expected`, such as
+    /// splitting a node that is not a text node.
+    UnexpectedType {
+        expected: NodeType,
+        found: NodeType,
+    },
+    /// A byte offset into a string of length `len` was past its end or not on
+    /// a character boundary.
+    InvalidOffset {
+        offset: usize,
+        len: usize,
+    },
+    /// A node of type `node_type` was nested more than `limit` levels deep,
+    /// too deep to convert.
+    TooDeep {
+        node_type: NodeType,
+        limit: usize,
+    },
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            NodeError::InvalidProperty { node_type, property } =>
+                write!(fmt, "{:?} nodes do not have property {}", node_type, property),
+            NodeError::InvalidValue { node_type, property } =>
+                write!(fmt, "invalid value for property {} of {:?} node", property, node_type),
+            NodeError::InteriorNul { node_type, property, position } =>
+                write!(fmt, "value for property {} of {:?} node contains a NUL byte at {}",
+                       property, node_type, position),
+            NodeError::InvalidChild { parent, child } =>
+                write!(fmt, "{:?} nodes cannot contain {:?} nodes", parent, child),
+            NodeError::NoParent { node_type } =>
+                write!(fmt, "{:?} node has no parent", node_type),
+            NodeError::UnexpectedType { expected, found } =>
+                write!(fmt, "expected a {:?} node, found a {:?} node", expected, found),
+            NodeError::InvalidOffset { offset, len } =>
+                write!(fmt, "offset {} is not a character boundary in a string of length {}",
+                       offset, len),
+            NodeError::TooDeep { node_type, limit } =>
+                write!(fmt, "{:?} node is nested more than {} levels deep", node_type, limit),
+        }
+    }
+}
+
+impl Error for NodeError {
+    fn description(&self) -> &str {
+        match *self {
+            NodeError::InvalidProperty { .. } => "property does not apply to node type",
+            NodeError::InvalidValue { .. } => "invalid property value",
+            NodeError::InteriorNul { .. } => "string value contains a NUL byte",
+            NodeError::InvalidChild { .. } => "invalid child node type",
+            NodeError::NoParent { .. } => "node has no parent",
+            NodeError::UnexpectedType { .. } => "unexpected node type",
+            NodeError::InvalidOffset { .. } => "invalid string offset",
+            NodeError::TooDeep { .. } => "nodes nested too deeply",
+        }
+    }
+}
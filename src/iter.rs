@@ -1,35 +1,43 @@
-use {raw, EventType, Node};
+use {raw, EventType, Node, NodeRef};
 use util::Binding;
 
-pub struct NodeIterator {
+use std::marker::PhantomData;
+
+/// An iterator over a node and all of its descendants, yielding an `Enter`
+/// event for every node and an `Exit` event for every node that may have
+/// children. The nodes it produces borrow from the tree being iterated.
+pub struct NodeIterator<'a> {
     raw: *mut raw::cmark_iter,
+    marker: PhantomData<&'a Node>,
 }
 
-impl NodeIterator {
-    pub fn from_raw(raw: *mut raw::cmark_iter) -> NodeIterator {
+impl<'a> NodeIterator<'a> {
+    pub unsafe fn from_raw(raw: *mut raw::cmark_iter) -> NodeIterator<'a> {
         NodeIterator {
             raw: raw,
+            marker: PhantomData,
         }
     }
 
     pub fn raw(&self) -> *mut raw::cmark_iter { self.raw }
 
-    pub fn new(root: &Node) -> NodeIterator {
-        let raw_iter = unsafe {
-            raw::cmark_iter_new(root.raw())
-        };
-        NodeIterator::from_raw(raw_iter)
+    pub fn new(root: &'a Node) -> NodeIterator<'a> {
+        unsafe {
+            NodeIterator::from_raw(raw::cmark_iter_new(root.raw()))
+        }
     }
 
-    pub fn reset(&mut self, current: &Node, event_type: EventType) {
+    /// Move the iterator to `current`, which must be in the tree being
+    /// iterated, as though it had just returned `event_type` for it.
+    pub fn reset(&mut self, current: &NodeRef<'a>, event_type: EventType) {
         unsafe {
             raw::cmark_iter_reset(self.raw, current.raw(), event_type.raw())
         }
     }
 
-    pub fn node(&self) -> Node {
+    pub fn node(&self) -> NodeRef<'a> {
         unsafe {
-            Node::from_raw(raw::cmark_iter_get_node(self.raw), false)
+            NodeRef::from_raw(raw::cmark_iter_get_node(self.raw))
         }
     }
 
@@ -39,14 +47,14 @@ impl NodeIterator {
         }
     }
 
-    pub fn root(&self) -> Node {
+    pub fn root(&self) -> NodeRef<'a> {
         unsafe {
-            Node::from_raw(raw::cmark_iter_get_root(self.raw), false)
+            NodeRef::from_raw(raw::cmark_iter_get_root(self.raw))
         }
     }
 }
 
-impl Drop for NodeIterator {
+impl<'a> Drop for NodeIterator<'a> {
     fn drop(&mut self) {
         unsafe {
             raw::cmark_iter_free(self.raw)
@@ -54,12 +62,15 @@ impl Drop for NodeIterator {
     }
 }
 
-impl Iterator for NodeIterator {
-    type Item = (EventType, Node);
+impl<'a> Iterator for NodeIterator<'a> {
+    type Item = (EventType, NodeRef<'a>);
 
-    fn next(&mut self) -> Option<(EventType, Node)> {
+    fn next(&mut self) -> Option<(EventType, NodeRef<'a>)> {
         let next_event_raw = unsafe { raw::cmark_iter_next(self.raw) };
-        if next_event_raw == raw::CMARK_EVENT_NONE {
+        // libcmark signals the end of the traversal with DONE, and keeps
+        // returning it (with a null node) on every subsequent call.
+        if next_event_raw == raw::CMARK_EVENT_NONE ||
+           next_event_raw == raw::CMARK_EVENT_DONE {
             None
         }
         else {
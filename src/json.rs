@@ -0,0 +1,522 @@
+use Inline;
+
+use std::error::Error;
+use std::fmt::{self, Write};
+
+/// An error from reading one of the JSON formats, such as with
+/// `parse_pandoc_json`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum JsonError {
+    /// The input is not valid JSON.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// The input is valid JSON, but not in the expected format. The `path`
+    /// locates the offending value, such as `blocks[2].c[0]`.
+    Format {
+        path: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            JsonError::Syntax { line, ref message } =>
+                write!(fmt, "invalid JSON on line {}: {}", line, message),
+            JsonError::Format { ref path, ref message } if path.is_empty() =>
+                fmt.write_str(message),
+            JsonError::Format { ref path, ref message } =>
+                write!(fmt, "at {}: {}", path, message),
+        }
+    }
+}
+
+impl Error for JsonError {
+    fn description(&self) -> &str {
+        match *self {
+            JsonError::Syntax { .. } => "invalid JSON",
+            JsonError::Format { .. } => "unexpected JSON structure",
+        }
+    }
+}
+
+/// An element that an importer could not represent in a CommonMark tree and
+/// left out.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DroppedElement {
+    /// Where the element was in the input, such as `blocks[2].c[0]`.
+    pub path: String,
+    /// The element's type in the input format.
+    pub element: String,
+}
+
+/// A JSON value. Object members keep their order, so that output is stable.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Build an object from `(key, value)` pairs.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn string(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+
+    /// Get the member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match *self {
+            Json::Object(ref members) =>
+                members.iter().find(|&&(ref name, _)| name == key).map(|&(_, ref value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Json {
+    /// Write the value as compact JSON.
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            Json::Null => fmt.write_str("null"),
+            Json::Bool(value) => write!(fmt, "{}", value),
+            Json::Number(value) => {
+                if value.fract() == 0.0 && value.abs() < 1e15 {
+                    write!(fmt, "{}", value as i64)
+                }
+                else {
+                    write!(fmt, "{}", value)
+                }
+            }
+            Json::String(ref value) => write_string(fmt, value),
+            Json::Array(ref values) => {
+                try!(fmt.write_char('['));
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        try!(fmt.write_char(','));
+                    }
+                    try!(value.fmt(fmt));
+                }
+                fmt.write_char(']')
+            }
+            Json::Object(ref members) => {
+                try!(fmt.write_char('{'));
+                for (index, &(ref key, ref value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        try!(fmt.write_char(','));
+                    }
+                    try!(write_string(fmt, key));
+                    try!(fmt.write_char(':'));
+                    try!(value.fmt(fmt));
+                }
+                fmt.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(fmt: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    try!(fmt.write_char('"'));
+    for c in value.chars() {
+        match c {
+            '"' => try!(fmt.write_str("\\\"")),
+            '\\' => try!(fmt.write_str("\\\\")),
+            '\n' => try!(fmt.write_str("\\n")),
+            '\r' => try!(fmt.write_str("\\r")),
+            '\t' => try!(fmt.write_str("\\t")),
+            c if (c as u32) < 0x20 => try!(write!(fmt, "\\u{:04x}", c as u32)),
+            c => try!(fmt.write_char(c)),
+        }
+    }
+    fmt.write_char('"')
+}
+
+/// How deeply arrays and objects may nest. The parser, and the importers
+/// that walk its output, recurse once per level, so deeper input is rejected
+/// rather than allowed to overflow the stack.
+const MAX_DEPTH: usize = 256;
+
+/// Parse a complete JSON document.
+///
+/// Arrays and objects nested more than `MAX_DEPTH` deep are a syntax error.
+pub fn parse(input: &str) -> Result<Json, JsonError> {
+    let mut parser = Parser {
+        input: input,
+        pos: 0,
+        line: 1,
+        depth: 0,
+    };
+    let value = try!(parser.value());
+    parser.skip_whitespace();
+    if parser.pos < input.len() {
+        return parser.error("trailing characters after the value");
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, JsonError> {
+        Err(JsonError::Syntax {
+            line: self.line,
+            message: message.to_string(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+            }
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if c != ' ' && c != '\t' && c != '\n' && c != '\r' {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, JsonError> {
+        if self.input[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(value)
+        }
+        else {
+            self.error("expected a value")
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.literal("null", Json::Null),
+            Some('t') => self.literal("true", Json::Bool(true)),
+            Some('f') => self.literal("false", Json::Bool(false)),
+            Some('"') => self.string().map(Json::String),
+            Some(c) if c == '[' || c == '{' => {
+                if self.depth == MAX_DEPTH {
+                    return self.error("arrays and objects nested too deeply");
+                }
+                self.depth += 1;
+                let value = if c == '[' { self.array() } else { self.object() };
+                self.depth -= 1;
+                value
+            }
+            Some(c) if c == '-' || c.is_digit(10) => self.number(),
+            _ => self.error("expected a value"),
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, JsonError> {
+        self.bump();
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(try!(self.value()));
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => (),
+                Some(']') => return Ok(Json::Array(values)),
+                _ => return self.error("expected ',' or ']'"),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, JsonError> {
+        self.bump();
+        let mut members = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return self.error("expected a string key");
+            }
+            let key = try!(self.string());
+            self.skip_whitespace();
+            if self.bump() != Some(':') {
+                return self.error("expected ':'");
+            }
+            members.push((key, try!(self.value())));
+            self.skip_whitespace();
+            match self.bump() {
+                Some(',') => (),
+                Some('}') => return Ok(Json::Object(members)),
+                _ => return self.error("expected ',' or '}'"),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+                break;
+            }
+            self.bump();
+        }
+        match self.input[start..self.pos].parse() {
+            Ok(number) => Ok(Json::Number(number)),
+            Err(_) => self.error("invalid number"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(out),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => try!(self.unicode_escape()),
+                        _ => return self.error("invalid escape"),
+                    };
+                    out.push(c);
+                }
+                Some(c) if (c as u32) < 0x20 => return self.error("control character in string"),
+                Some(c) => out.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, JsonError> {
+        // Checked by hand, since `from_str_radix` would also accept a sign.
+        let value = match self.input.get(self.pos..self.pos + 4) {
+            Some(digits) if digits.bytes().all(|b| b.is_ascii_hexdigit()) =>
+                u32::from_str_radix(digits, 16).unwrap(),
+            _ => return self.error("invalid \\u escape"),
+        };
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, JsonError> {
+        let mut code = try!(self.hex4());
+        if code >= 0xD800 && code < 0xDC00 {
+            // A high surrogate, which must be followed by a low one.
+            if !self.input[self.pos..].starts_with("\\u") {
+                return self.error("unpaired surrogate");
+            }
+            self.pos += 2;
+            let low = try!(self.hex4());
+            if low < 0xDC00 || low >= 0xE000 {
+                return self.error("unpaired surrogate");
+            }
+            code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+        }
+        match ::std::char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.error("unpaired surrogate"),
+        }
+    }
+}
+
+/// Reads values out of a parsed document, keeping track of where they are
+/// so that format errors can say which value was wrong.
+pub struct Reader<'a> {
+    pub json: &'a Json,
+    pub path: String,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(json: &'a Json, path: &str) -> Reader<'a> {
+        Reader {
+            json: json,
+            path: path.to_string(),
+        }
+    }
+
+    pub fn error<T>(&self, message: &str) -> Result<T, JsonError> {
+        Err(JsonError::Format {
+            path: self.path.clone(),
+            message: message.to_string(),
+        })
+    }
+
+    /// Describe this value, an element of type `element`, as one that an
+    /// importer left out.
+    pub fn dropped(&self, element: &str) -> DroppedElement {
+        DroppedElement {
+            path: self.path.clone(),
+            element: element.to_string(),
+        }
+    }
+
+    fn expected<T>(&self, what: &str) -> Result<T, JsonError> {
+        self.error(&format!("expected {}", what))
+    }
+
+    /// Get the member `key`, which must be present.
+    pub fn field(&self, key: &str) -> Result<Reader<'a>, JsonError> {
+        match self.json {
+            &Json::Object(_) => (),
+            _ => return self.expected("an object"),
+        }
+        match self.json.get(key) {
+            Some(json) => Ok(self.member(json, key)),
+            None => self.error(&format!("missing {:?}", key)),
+        }
+    }
+
+    /// Get the member `key`, if it is present and not null.
+    pub fn optional(&self, key: &str) -> Option<Reader<'a>> {
+        match self.json.get(key) {
+            None | Some(&Json::Null) => None,
+            Some(json) => Some(self.member(json, key)),
+        }
+    }
+
+    fn member(&self, json: &'a Json, key: &str) -> Reader<'a> {
+        let path = if self.path.is_empty() { key.to_string() } else { format!("{}.{}", self.path, key) };
+        Reader {
+            json: json,
+            path: path,
+        }
+    }
+
+    /// Get the elements of an array.
+    pub fn items(&self) -> Result<Vec<Reader<'a>>, JsonError> {
+        match *self.json {
+            Json::Array(ref values) => Ok(values.iter().enumerate().map(|(index, json)| Reader {
+                json: json,
+                path: format!("{}[{}]", self.path, index),
+            }).collect()),
+            _ => self.expected("an array"),
+        }
+    }
+
+    /// Get the elements of an array that must have exactly `len` of them.
+    pub fn tuple(&self, len: usize) -> Result<Vec<Reader<'a>>, JsonError> {
+        let items = try!(self.items());
+        if items.len() != len {
+            return self.error(&format!("expected an array of {} elements", len));
+        }
+        Ok(items)
+    }
+
+    pub fn str(&self) -> Result<&'a str, JsonError> {
+        match *self.json {
+            Json::String(ref value) => Ok(value),
+            _ => self.expected("a string"),
+        }
+    }
+
+    pub fn bool(&self) -> Result<bool, JsonError> {
+        match *self.json {
+            Json::Bool(value) => Ok(value),
+            _ => self.expected("true or false"),
+        }
+    }
+
+    pub fn i32(&self) -> Result<i32, JsonError> {
+        match *self.json {
+            Json::Number(value) if value.fract() == 0.0 && value.abs() <= i32::max_value() as f64 =>
+                Ok(value as i32),
+            _ => self.expected("an integer"),
+        }
+    }
+}
+
+/// Add text to the end of `out`, joining it to any text already there.
+pub fn push_text(out: &mut Vec<Inline>, text: &str) {
+    if let Some(&mut Inline::Text(ref mut last)) = out.last_mut() {
+        last.push_str(text);
+        return;
+    }
+    out.push(Inline::Text(text.to_string()));
+}
+
+/// Add `inlines` to the end of `out`, as with `push_text` for text.
+pub fn extend_inlines(out: &mut Vec<Inline>, inlines: Vec<Inline>) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => push_text(out, &text),
+            inline => out.push(inline),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_write() {
+        let input = "{\"a\": [1, -2.5, true, null],\n \"b\\u00e9\": \"x\\\"\\ud83d\\ude00\\n\"}";
+        let json = parse(input).unwrap();
+        assert_eq!(json.get("bé"), Some(&Json::string("x\"\u{1F600}\n")));
+        assert_eq!(json.to_string(), "{\"a\":[1,-2.5,true,null],\"bé\":\"x\\\"\u{1F600}\\n\"}");
+
+        assert_eq!(parse("[1,\n2,\n]"), Err(JsonError::Syntax {
+            line: 3,
+            message: "expected a value".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(parse("\"\\u+041\"").is_err());
+        assert!(parse("\"\\u00e\"").is_err());
+        assert_eq!(parse("\"\\u00E9\""), Ok(Json::string("\u{e9}")));
+
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert_eq!(parse(&nested(100000)), Err(JsonError::Syntax {
+            line: 1,
+            message: "arrays and objects nested too deeply".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_reader_paths() {
+        let json = parse("{\"blocks\": [{\"c\": [1, \"two\"]}]}").unwrap();
+        let reader = Reader::new(&json, "");
+        let blocks = reader.field("blocks").unwrap().items().unwrap();
+        let c = blocks[0].field("c").unwrap().tuple(2).unwrap();
+        assert_eq!(c[0].i32(), Ok(1));
+        assert_eq!(c[1].i32().unwrap_err().to_string(), "at blocks[0].c[1]: expected an integer");
+    }
+}
@@ -0,0 +1,195 @@
+use {Node, NodeType, ListType, DelimType};
+
+/// A typed view of a node, obtained through `Node::kind`. Node types with
+/// type-specific properties carry a view that exposes only the accessors that
+/// are valid for that type, so matching on a `NodeKind` rules out invalid
+/// property access at compile time.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, NodeKind, DEFAULT};
+///
+/// let doc = parse_document("## Hello", DEFAULT);
+/// match doc.first_child().unwrap().kind() {
+///     NodeKind::Header(header) => assert_eq!(header.level(), 2),
+///     _ => panic!("expected a header"),
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub enum NodeKind<'a> {
+    None,
+    Document,
+    BlockQuote,
+    List(ListNode<'a>),
+    Item,
+    CodeBlock(CodeBlockNode<'a>),
+    Html(LiteralNode<'a>),
+    Paragraph,
+    Header(HeaderNode<'a>),
+    Hrule,
+    Text(LiteralNode<'a>),
+    SoftBreak,
+    LineBreak,
+    Code(LiteralNode<'a>),
+    InlineHtml(LiteralNode<'a>),
+    Emph,
+    Strong,
+    Link(LinkNode<'a>),
+    Image(LinkNode<'a>),
+}
+
+impl<'a> NodeKind<'a> {
+    /// Classify `node` by its type.
+    pub fn of(node: &'a Node) -> NodeKind<'a> {
+        match node.node_type() {
+            NodeType::None => NodeKind::None,
+            NodeType::Document => NodeKind::Document,
+            NodeType::BlockQuote => NodeKind::BlockQuote,
+            NodeType::List => NodeKind::List(ListNode { node: node }),
+            NodeType::Item => NodeKind::Item,
+            NodeType::CodeBlock => NodeKind::CodeBlock(CodeBlockNode { node: node }),
+            NodeType::Html => NodeKind::Html(LiteralNode { node: node }),
+            NodeType::Paragraph => NodeKind::Paragraph,
+            NodeType::Header => NodeKind::Header(HeaderNode { node: node }),
+            NodeType::Hrule => NodeKind::Hrule,
+            NodeType::Text => NodeKind::Text(LiteralNode { node: node }),
+            NodeType::SoftBreak => NodeKind::SoftBreak,
+            NodeType::LineBreak => NodeKind::LineBreak,
+            NodeType::Code => NodeKind::Code(LiteralNode { node: node }),
+            NodeType::InlineHtml => NodeKind::InlineHtml(LiteralNode { node: node }),
+            NodeType::Emph => NodeKind::Emph,
+            NodeType::Strong => NodeKind::Strong,
+            NodeType::Link => NodeKind::Link(LinkNode { node: node }),
+            NodeType::Image => NodeKind::Image(LinkNode { node: node }),
+        }
+    }
+}
+
+/// A `Header` node.
+#[derive(Clone, Copy, Debug)]
+pub struct HeaderNode<'a> {
+    node: &'a Node,
+}
+
+impl<'a> HeaderNode<'a> {
+    /// Get the underlying node.
+    pub fn node(&self) -> &'a Node { self.node }
+
+    /// Get the level of this header, from 1 to 6.
+    pub fn level(&self) -> i32 {
+        self.node.header_level()
+    }
+}
+
+/// A `List` node.
+#[derive(Clone, Copy, Debug)]
+pub struct ListNode<'a> {
+    node: &'a Node,
+}
+
+impl<'a> ListNode<'a> {
+    /// Get the underlying node.
+    pub fn node(&self) -> &'a Node { self.node }
+
+    /// Get whether this is a bullet or ordered list.
+    pub fn list_type(&self) -> ListType {
+        self.node.list_type()
+    }
+
+    /// Get the delimiter used by an ordered list.
+    pub fn delim(&self) -> DelimType {
+        self.node.list_delim()
+    }
+
+    /// Get the number of the first item in an ordered list.
+    pub fn start(&self) -> i32 {
+        self.node.list_start()
+    }
+
+    /// Get whether this list is tight, meaning its items are not separated by
+    /// blank lines.
+    pub fn tight(&self) -> bool {
+        self.node.list_tight()
+    }
+}
+
+/// A `CodeBlock` node.
+#[derive(Clone, Copy, Debug)]
+pub struct CodeBlockNode<'a> {
+    node: &'a Node,
+}
+
+impl<'a> CodeBlockNode<'a> {
+    /// Get the underlying node.
+    pub fn node(&self) -> &'a Node { self.node }
+
+    /// Get the info string following the opening fence, which is empty for
+    /// indented code blocks.
+    pub fn fence_info(&self) -> &'a str {
+        self.node.fence_info()
+    }
+
+    /// Get the contents of the code block.
+    pub fn literal(&self) -> &'a str {
+        self.node.literal()
+    }
+}
+
+/// A node whose only property is its string contents: `Text`, `Code`, `Html`
+/// or `InlineHtml`.
+#[derive(Clone, Copy, Debug)]
+pub struct LiteralNode<'a> {
+    node: &'a Node,
+}
+
+impl<'a> LiteralNode<'a> {
+    /// Get the underlying node.
+    pub fn node(&self) -> &'a Node { self.node }
+
+    /// Get the string contents of this node.
+    pub fn literal(&self) -> &'a str {
+        self.node.literal()
+    }
+}
+
+/// A `Link` or `Image` node.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkNode<'a> {
+    node: &'a Node,
+}
+
+impl<'a> LinkNode<'a> {
+    /// Get the underlying node.
+    pub fn node(&self) -> &'a Node { self.node }
+
+    /// Get the destination of the link, or the source of the image.
+    pub fn url(&self) -> &'a str {
+        self.node.url()
+    }
+
+    /// Get the title, which is empty if none was given.
+    pub fn title(&self) -> &'a str {
+        self.node.title()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{Node, NodeType, ListType};
+
+    #[test]
+    fn test_list_kind() {
+        let mut list = Node::new(NodeType::List);
+        list.set_list_type(ListType::Ordered).unwrap();
+        list.set_list_start(3).unwrap();
+        match list.kind() {
+            NodeKind::List(view) => {
+                assert_eq!(view.list_type(), ListType::Ordered);
+                assert_eq!(view.start(), 3);
+            }
+            other => panic!("unexpected kind {:?}", other),
+        }
+    }
+}
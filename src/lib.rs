@@ -14,7 +14,13 @@
 //! programmatically or by parsing CommonMark source. Nodes all have a type and
 //! may have parent, child, and sibling nodes. Depending on the node type, a
 //! variety of properties are available. If a property is not applicable to a
-//! given node type, then attempting to access it will panic.
+//! given node type, then attempting to access it will panic. The `try_`
+//! variants of each getter, and all of the setters, return a `NodeError`
+//! instead.
+//!
+//! Parsed trees are returned as a `Document`, which owns every node in the
+//! tree. Walking the tree produces `NodeRef` handles that borrow from the
+//! document, so they cannot be used once it has been dropped.
 //!
 //!```
 //! use rcmark::{Node, NodeType, ListType};
@@ -22,15 +28,17 @@
 //! let mut root = Node::new(NodeType::Document);
 //!
 //! let mut heading = Node::new(NodeType::Header);
-//! heading.set_header_level(1);
+//! heading.set_header_level(1).unwrap();
 //!
 //! let mut heading_text = Node::new(NodeType::Text);
-//! heading_text.set_literal("Hello, World!");
+//! heading_text.set_literal("Hello, World!").unwrap();
 //!
-//! heading.prepend_child(&mut heading_text);
-//! root.prepend_child(&mut heading);
+//! heading.prepend_child(heading_text).unwrap();
+//! root.prepend_child(heading).unwrap();
 //!```
 //!
+//! The `md!` macro builds the same kind of tree declaratively.
+//!
 //!## Parsing a Document
 //!
 //! Parsing can be done through either a `Parser` instance or
@@ -70,16 +78,61 @@
 //! assert_eq!(rcmark::render_commonmark(&doc, rcmark::DEFAULT, 2),
 //!            "# Hello\n");
 //!```
+//!
+//!## Serialization
+//!
+//! With the `serde` feature enabled, `Document`, `Block` and `Inline`
+//! implement `Serialize` and `Deserialize`. A document is written as an
+//! object holding the schema version, currently `SCHEMA_VERSION` (1), and
+//! the root node:
+//!
+//!```text
+//! {"version": 1, "document": {"type": "document", "children": [...], "pos": {...}}}
+//!```
+//!
+//! Every node is an object whose `type` is the node's `type_string`, such as
+//! `paragraph` or `code_block`. Its type-specific properties follow under the
+//! names of their getters: `literal`, `header_level`, `list_type` (`bullet`,
+//! `ordered` or `none`), `list_delim` (`period`, `paren` or `none`),
+//! `list_start`, `list_tight`, `fence_info`, `url` and `title`. Containers
+//! have a `children` array. Blocks have a `pos` object with `start_line`,
+//! `start_column`, `end_line` and `end_column`, or `null` if they have no
+//! position; it may be left out when deserializing, as may a link's `title`.
+//!
+//! Deserializing a document rebuilds its libcmark tree, so it renders exactly
+//! as the original did. Source positions cannot be restored.
 #![feature(concat_idents)]
 // #![deny(missing_docs)]
 
 extern crate libc;
 extern crate libcmark_sys as raw;
 #[macro_use] extern crate bitflags;
+#[cfg(feature = "serde")] extern crate serde;
+#[cfg(feature = "serde")] #[macro_use] extern crate serde_derive;
+#[cfg(all(test, feature = "serde"))] extern crate serde_json;
 
-pub use node::Node;
+pub use node::{Node, NodeRef, NodeMut};
+pub use ast::{Block, Inline, SourcePos, MAX_DEPTH};
+pub use axis::{Siblings, Ancestors, Descendants};
+pub use builder::{DocumentBuilder, BlockBuilder, ListBuilder, InlineBuilder};
+pub use compare::Positions;
+pub use cursor::TreeCursor;
+pub use document::Document;
+pub use error::NodeError;
+pub use kind::{NodeKind, HeaderNode, ListNode, CodeBlockNode, LiteralNode, LinkNode};
 pub use iter::NodeIterator;
+pub use json::{JsonError, DroppedElement};
+pub use mdast::{render_mdast, parse_mdast};
+pub use pandoc::{render_pandoc_json, parse_pandoc_json};
 pub use parser::{Parser, parse_document};
+pub use position::PositionIndex;
+pub use select::{Selector, SelectorError};
+#[cfg(feature = "serde")] pub use serialize::SCHEMA_VERSION;
+pub use source::SourceMap;
+pub use text::TextOptions;
+pub use transform::{Transform, Action, Pipeline, PipelineReport, PassReport};
+pub use visit::{Visitor, VisitControl};
+pub use xml::{parse_xml, XmlError};
 pub use render::{render_xml, render_html, render_man, render_commonmark};
 
 use util::Binding;
@@ -87,11 +140,36 @@ use util::Binding;
 use std::ffi::CStr;
 use std::str;
 
+#[macro_use] mod macros;
+
 mod node;
+mod ast;
+mod axis;
+mod builder;
+mod clone;
+mod compare;
+mod cursor;
+mod data;
+mod document;
+mod dump;
+mod edit;
+mod error;
 mod iter;
+mod json;
+mod kind;
+mod mdast;
+mod pandoc;
 mod parser;
+mod position;
 mod render;
+mod select;
+#[cfg(feature = "serde")] mod serialize;
+mod source;
+mod text;
+mod transform;
 mod util;
+mod visit;
+mod xml;
 
 /// The types of nodes that make up a CommonMark document.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
@@ -172,9 +250,69 @@ impl Binding for NodeType {
     }
 }
 
+impl NodeType {
+    /// Whether nodes of this type are block-level elements.
+    pub fn is_block(&self) -> bool {
+        match *self {
+            NodeType::Document | NodeType::BlockQuote | NodeType::List |
+            NodeType::Item | NodeType::CodeBlock | NodeType::Html |
+            NodeType::Paragraph | NodeType::Header | NodeType::Hrule => true,
+            _ => false,
+        }
+    }
+
+    /// Whether nodes of this type are inline elements.
+    pub fn is_inline(&self) -> bool {
+        match *self {
+            NodeType::None => false,
+            _ => !self.is_block(),
+        }
+    }
+
+    /// Whether nodes of this type can never have children. A `NodeIterator`
+    /// only produces an `Enter` event for these nodes, never an `Exit`.
+    pub fn is_leaf(&self) -> bool {
+        match *self {
+            NodeType::CodeBlock | NodeType::Html | NodeType::Hrule |
+            NodeType::Text | NodeType::SoftBreak | NodeType::LineBreak |
+            NodeType::Code | NodeType::InlineHtml => true,
+            _ => false,
+        }
+    }
+
+    /// Whether libcmark allows a node of this type to have a child of type
+    /// `child`. Containers such as block quotes and list items hold blocks
+    /// other than items, lists hold only items, and paragraphs, headers and
+    /// inline containers hold inlines.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::NodeType;
+    ///
+    /// assert!(NodeType::List.can_contain(NodeType::Item));
+    /// assert!(!NodeType::Document.can_contain(NodeType::Text));
+    /// ```
+    pub fn can_contain(&self, child: NodeType) -> bool {
+        match *self {
+            NodeType::Document | NodeType::BlockQuote | NodeType::Item =>
+                child.is_block() && child != NodeType::Item && child != NodeType::Document,
+            NodeType::List =>
+                child == NodeType::Item,
+            NodeType::Paragraph | NodeType::Header | NodeType::Emph |
+            NodeType::Strong | NodeType::Link | NodeType::Image =>
+                child.is_inline(),
+            _ => false,
+        }
+    }
+}
+
 /// The type of CommonMark list.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum ListType {
+    #[cfg_attr(feature = "serde", serde(rename = "none"))]
     NoList,
     Bullet,
     Ordered
@@ -202,7 +340,10 @@ impl Binding for ListType {
 
 // The type of list delimiter in an ordered list.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum DelimType {
+    #[cfg_attr(feature = "serde", serde(rename = "none"))]
     NoDelim,
     /// Numbers are written as `1.`
     Period,
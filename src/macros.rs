@@ -0,0 +1,216 @@
+/// Build a `Document` declaratively. The macro expands into `Node::new` and
+/// the usual setters and `append_child` calls, so the result is exactly what
+/// building the tree by hand would produce.
+///
+/// The body of `doc { ... }` is a sequence of elements, each of which may
+/// contain further elements in braces:
+///
+/// * Blocks: `h1 { ... }` through `h6 { ... }`, `p { ... }`,
+///   `blockquote { ... }`, `ul { ... }`, `ol { ... }`, `ol(start) { ... }`,
+///   `li { ... }`, `code_block(info, literal)`, `html(literal)` and `hr`.
+/// * Inlines: string literals, `text(expr)`, `em { ... }`, `strong { ... }`,
+///   `code(literal)`, `inline_html(literal)`, `link(url, title) { ... }`,
+///   `image(url, title) { ... }`, `softbreak` and `linebreak`.
+///
+/// Lists built with `ul` and `ol` are tight. Each element is handled by a
+/// recursive expansion, so very long runs of siblings may need a larger
+/// `#![recursion_limit]`.
+///
+/// # Panics
+///
+/// If an element is placed somewhere libcmark does not allow it, such as an
+/// `li` outside of a list, or if a literal contains a NUL byte.
+///
+/// # Examples
+///
+/// ```
+/// #[macro_use] extern crate rcmark;
+///
+/// fn main() {
+///     let doc = md!{
+///         doc {
+///             h1 { "Hello" }
+///             p {
+///                 "plain " em { "emphasis" } " "
+///                 link("https://x", "title") { "text" }
+///             }
+///         }
+///     };
+///
+///     assert_eq!(rcmark::render_html(&doc, rcmark::DEFAULT),
+///                "<h1>Hello</h1>\n\
+///                 <p>plain <em>emphasis</em> <a href=\"https://x\" title=\"title\">text</a></p>\n");
+/// }
+/// ```
+///
+/// The macro can also be called by its path, without `#[macro_use]`:
+///
+/// ```
+/// extern crate rcmark;
+///
+/// fn main() {
+///     let doc = rcmark::md!{ doc { p { "Hi " em { "there" } } } };
+///     assert_eq!(rcmark::render_html(&doc, rcmark::DEFAULT),
+///                "<p>Hi <em>there</em></p>\n");
+/// }
+/// ```
+#[macro_export]
+macro_rules! md {
+    (doc { $($body:tt)* }) => {{
+        let mut root = $crate::Node::new($crate::NodeType::Document);
+        $crate::md!(@children root; $($body)*);
+        $crate::Document::from(root)
+    }};
+
+    // Blocks
+    (@children $parent:ident; h1 { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@header $parent, 1, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; h2 { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@header $parent, 2, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; h3 { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@header $parent, 3, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; h4 { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@header $parent, 4, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; h5 { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@header $parent, 5, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; h6 { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@header $parent, 6, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; p { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::Paragraph), { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; blockquote { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::BlockQuote), { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; ul { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@list $parent, $crate::ListType::Bullet, $crate::DelimType::NoDelim, 1, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; ol { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@list $parent, $crate::ListType::Ordered, $crate::DelimType::Period, 1, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; ol($start:expr) { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@list $parent, $crate::ListType::Ordered, $crate::DelimType::Period, $start, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; li { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::Item), { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; code_block($info:expr, $literal:expr) $($rest:tt)*) => {
+        $crate::md!(@append $parent, {
+            let mut node = $crate::Node::new($crate::NodeType::CodeBlock);
+            node.set_fence_info($info).unwrap();
+            node.set_literal($literal).unwrap();
+            node
+        }, {});
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; html($literal:expr) $($rest:tt)*) => {
+        $crate::md!(@literal $parent, $crate::NodeType::Html, $literal);
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; hr $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::Hrule), {});
+        $crate::md!(@children $parent; $($rest)*);
+    };
+
+    // Inlines
+    (@children $parent:ident; em { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::Emph), { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; strong { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::Strong), { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; code($literal:expr) $($rest:tt)*) => {
+        $crate::md!(@literal $parent, $crate::NodeType::Code, $literal);
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; inline_html($literal:expr) $($rest:tt)*) => {
+        $crate::md!(@literal $parent, $crate::NodeType::InlineHtml, $literal);
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; link($url:expr, $title:expr) { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@link $parent, $crate::NodeType::Link, $url, $title, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; image($url:expr, $title:expr) { $($body:tt)* } $($rest:tt)*) => {
+        $crate::md!(@link $parent, $crate::NodeType::Image, $url, $title, { $($body)* });
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; softbreak $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::SoftBreak), {});
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; linebreak $($rest:tt)*) => {
+        $crate::md!(@append $parent, $crate::Node::new($crate::NodeType::LineBreak), {});
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; text($literal:expr) $($rest:tt)*) => {
+        $crate::md!(@literal $parent, $crate::NodeType::Text, $literal);
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; $literal:tt $($rest:tt)*) => {
+        $crate::md!(@literal $parent, $crate::NodeType::Text, $literal);
+        $crate::md!(@children $parent; $($rest)*);
+    };
+    (@children $parent:ident; ) => {};
+
+    // Helpers
+    (@append $parent:ident, $child:expr, { $($body:tt)* }) => {{
+        let mut child = $child;
+        $crate::md!(@children child; $($body)*);
+        if let Err(child) = $parent.append_child(child) {
+            panic!("{} nodes cannot contain {} nodes",
+                   $parent.type_string(), child.type_string());
+        }
+    }};
+    (@literal $parent:ident, $node_type:expr, $literal:expr) => {
+        $crate::md!(@append $parent, {
+            let mut node = $crate::Node::new($node_type);
+            node.set_literal($literal).unwrap();
+            node
+        }, {});
+    };
+    (@header $parent:ident, $level:expr, { $($body:tt)* }) => {
+        $crate::md!(@append $parent, {
+            let mut node = $crate::Node::new($crate::NodeType::Header);
+            node.set_header_level($level).unwrap();
+            node
+        }, { $($body)* });
+    };
+    (@list $parent:ident, $list_type:expr, $delim:expr, $start:expr, { $($body:tt)* }) => {
+        $crate::md!(@append $parent, {
+            let mut node = $crate::Node::new($crate::NodeType::List);
+            node.set_list_type($list_type).unwrap();
+            node.set_list_delim($delim).unwrap();
+            node.set_list_start($start).unwrap();
+            node.set_list_tight(true).unwrap();
+            node
+        }, { $($body)* });
+    };
+    (@link $parent:ident, $node_type:expr, $url:expr, $title:expr, { $($body:tt)* }) => {
+        $crate::md!(@append $parent, {
+            let mut node = $crate::Node::new($node_type);
+            node.set_url($url).unwrap();
+            node.set_title($title).unwrap();
+            node
+        }, { $($body)* });
+    };
+}
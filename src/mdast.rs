@@ -0,0 +1,445 @@
+use {Node, NodeType, Block, Inline, SourcePos, Document, ListType, DelimType, NodeError};
+use json::{self, Json, JsonError, Reader, DroppedElement};
+
+use std::cmp;
+
+/// Render a tree as mdast, the syntax tree format of unified and remark, in
+/// JSON.
+///
+/// Each node becomes the matching mdast node: headers are `heading` with a
+/// `depth`, lists are `list` with `ordered`, `start` and `spread`, code
+/// blocks are `code` with the info string split into `lang` and `meta`, and
+/// HTML blocks and inline HTML are both `html`. Soft line breaks become a
+/// newline in the surrounding `text`, as remark does, and the alt text of an
+/// image is flattened to its `alt` string. Blocks that have a source position
+/// get an mdast `position`, whose end column is exclusive where libcmark's is
+/// inclusive. Rendering a node other than a document gives just that node,
+/// rather than a `root`; a list item still takes its `spread` from its list.
+///
+/// # Errors
+///
+/// Returns `NodeError::TooDeep` if the tree is nested more than `MAX_DEPTH`
+/// levels deep, as with `Block::from_node`.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, render_mdast, DEFAULT};
+///
+/// let doc = parse_document("## Hi\n", DEFAULT);
+/// assert_eq!(render_mdast(&doc.first_child().unwrap()).unwrap(),
+///            "{\"type\":\"heading\",\"depth\":2,\"children\":[{\"type\":\"text\",\"value\":\"Hi\"}],\
+///             \"position\":{\"start\":{\"line\":1,\"column\":1},\"end\":{\"line\":1,\"column\":6}}}");
+/// ```
+pub fn render_mdast(root: &Node) -> Result<String, NodeError> {
+    if root.node_type().is_block() {
+        // An item on its own is spread if the list it is in is loose.
+        let spread = match root.parent() {
+            Some(ref list) if list.node_type() == NodeType::List => !list.list_tight(),
+            _ => false,
+        };
+        Ok(write_block(&try!(Block::from_node(root)), spread).to_string())
+    }
+    else {
+        let mut out = Vec::new();
+        write_inline(&try!(Inline::from_node(root)), &mut out);
+        Ok(out.pop().unwrap_or_else(|| text_node(String::new())).to_string())
+    }
+}
+
+/// Read a document from mdast JSON, such as remark produces.
+///
+/// The input must be a `root`. Only the node types that an rcmark tree can
+/// hold are kept. The others, such as tables, footnotes, frontmatter, and the
+/// `definition` and `imageReference` nodes of a tree whose references were
+/// left unresolved, are dropped and returned alongside the document.
+/// `delete` and `linkReference` nodes are reported too, but their children
+/// are kept in their place. Newlines in `text` become soft line breaks, and a
+/// list is tight unless it or any of its items is `spread`. The `position` of
+/// each node is ignored, since libcmark has no way to set source positions.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_mdast, render_html, DEFAULT};
+///
+/// let json = "{\"type\":\"root\",\"children\":[{\"type\":\"code\",\"lang\":\"sh\",\
+///             \"meta\":null,\"value\":\"ls\"},{\"type\":\"yaml\",\"value\":\"a: 1\"}]}";
+/// let (doc, dropped) = parse_mdast(json).unwrap();
+/// assert_eq!(render_html(&doc, DEFAULT),
+///            "<pre><code class=\"language-sh\">ls\n</code></pre>\n");
+/// assert_eq!(dropped[0].element, "yaml");
+/// assert_eq!(dropped[0].path, "children[1]");
+/// ```
+pub fn parse_mdast(input: &str) -> Result<(Document, Vec<DroppedElement>), JsonError> {
+    let json = try!(json::parse(input));
+    let root = Reader::new(&json, "");
+    if try!(try!(root.field("type")).str()) != "root" {
+        return root.error("expected a root node");
+    }
+
+    let mut importer = Importer { dropped: Vec::new() };
+    let children = try!(importer.blocks(&root));
+    let doc = Block::Document { children: children, pos: None };
+    match doc.to_document() {
+        Ok(doc) => Ok((doc, importer.dropped)),
+        Err(err) => root.error(&err.to_string()),
+    }
+}
+
+fn optional_string(value: &str) -> Json {
+    if value.is_empty() { Json::Null } else { Json::string(value) }
+}
+
+fn text_node(value: String) -> Json {
+    Json::object(vec![("type", Json::string("text")), ("value", Json::String(value))])
+}
+
+fn point(line: i32, column: i32) -> Json {
+    Json::object(vec![("line", Json::Number(line as f64)), ("column", Json::Number(column as f64))])
+}
+
+/// Build a node, with `children` if it is a parent and `position` if it has
+/// one.
+fn node(node_type: &str, mut members: Vec<(&str, Json)>, children: Option<Vec<Json>>,
+        pos: Option<SourcePos>) -> Json {
+    members.insert(0, ("type", Json::string(node_type)));
+    members.extend(children.map(|children| ("children", Json::Array(children))));
+    members.extend(pos.map(|pos| ("position", Json::object(vec![
+        ("start", point(pos.start_line, pos.start_column)),
+        ("end", point(pos.end_line, pos.end_column + 1)),
+    ]))));
+    Json::object(members)
+}
+
+fn write_blocks(blocks: &[Block], spread: bool) -> Vec<Json> {
+    blocks.iter().map(|block| write_block(block, spread)).collect()
+}
+
+/// Convert a block. `spread` is only used by list items, which take it from
+/// their list.
+fn write_block(block: &Block, spread: bool) -> Json {
+    let pos = block.pos();
+    match *block {
+        Block::Document { ref children, .. } =>
+            node("root", Vec::new(), Some(write_blocks(children, false)), pos),
+        Block::BlockQuote { ref children, .. } =>
+            node("blockquote", Vec::new(), Some(write_blocks(children, false)), pos),
+        Block::List { list_type, start, tight, ref children, .. } => {
+            let ordered = list_type == ListType::Ordered;
+            node("list", vec![
+                ("ordered", Json::Bool(ordered)),
+                ("start", if ordered { Json::Number(start as f64) } else { Json::Null }),
+                ("spread", Json::Bool(!tight)),
+            ], Some(write_blocks(children, !tight)), pos)
+        }
+        Block::Item { ref children, .. } =>
+            node("listItem", vec![("spread", Json::Bool(spread))], Some(write_blocks(children, false)), pos),
+        Block::CodeBlock { ref info, ref literal, .. } => {
+            let info = info.trim();
+            let (lang, meta) = match info.find(char::is_whitespace) {
+                Some(index) => (&info[..index], info[index..].trim_start()),
+                None => (info, ""),
+            };
+            let value = if literal.ends_with('\n') { &literal[..literal.len() - 1] } else { &literal[..] };
+            node("code", vec![
+                ("lang", optional_string(lang)),
+                ("meta", optional_string(meta)),
+                ("value", Json::string(value)),
+            ], None, pos)
+        }
+        Block::Html { ref literal, .. } =>
+            node("html", vec![("value", Json::string(literal.trim_end_matches('\n')))], None, pos),
+        Block::Paragraph { ref children, .. } =>
+            node("paragraph", Vec::new(), Some(write_inlines(children)), pos),
+        Block::Heading { level, ref children, .. } =>
+            node("heading", vec![("depth", Json::Number(level as f64))], Some(write_inlines(children)), pos),
+        Block::Hrule { .. } => node("thematicBreak", Vec::new(), None, pos),
+    }
+}
+
+fn write_inlines(inlines: &[Inline]) -> Vec<Json> {
+    let mut out = Vec::new();
+    for inline in inlines {
+        write_inline(inline, &mut out);
+    }
+    out
+}
+
+fn write_inline(inline: &Inline, out: &mut Vec<Json>) {
+    let json = match *inline {
+        Inline::Text(ref literal) => {
+            push_text(out, literal);
+            return;
+        }
+        Inline::SoftBreak => {
+            push_text(out, "\n");
+            return;
+        }
+        Inline::LineBreak => node("break", Vec::new(), None, None),
+        Inline::Code(ref literal) => node("inlineCode", vec![("value", Json::string(literal))], None, None),
+        Inline::InlineHtml(ref literal) => node("html", vec![("value", Json::string(literal))], None, None),
+        Inline::Emph(ref children) => node("emphasis", Vec::new(), Some(write_inlines(children)), None),
+        Inline::Strong(ref children) => node("strong", Vec::new(), Some(write_inlines(children)), None),
+        Inline::Link { ref url, ref title, ref children } =>
+            node("link", vec![("url", Json::string(url)), ("title", optional_string(title))],
+                 Some(write_inlines(children)), None),
+        Inline::Image { ref url, ref title, ref children } => {
+            let mut alt = String::new();
+            alt_text(children, &mut alt);
+            node("image", vec![
+                ("url", Json::string(url)),
+                ("title", optional_string(title)),
+                ("alt", Json::String(alt)),
+            ], None, None)
+        }
+    };
+    out.push(json);
+}
+
+/// Add text to the end of `out`, joining it to a `text` node already there.
+fn push_text(out: &mut Vec<Json>, text: &str) {
+    if let Some(&mut Json::Object(ref mut members)) = out.last_mut() {
+        if members[0].1 == Json::string("text") {
+            if let Json::String(ref mut value) = members[1].1 {
+                value.push_str(text);
+                return;
+            }
+        }
+    }
+    out.push(text_node(text.to_string()));
+}
+
+fn alt_text(inlines: &[Inline], out: &mut String) {
+    for inline in inlines {
+        match *inline {
+            Inline::Text(ref literal) | Inline::Code(ref literal) => out.push_str(literal),
+            Inline::SoftBreak | Inline::LineBreak => out.push(' '),
+            Inline::InlineHtml(_) => (),
+            Inline::Emph(ref children) | Inline::Strong(ref children) |
+            Inline::Link { ref children, .. } | Inline::Image { ref children, .. } =>
+                alt_text(children, out),
+        }
+    }
+}
+
+struct Importer {
+    dropped: Vec<DroppedElement>,
+}
+
+impl Importer {
+    /// Convert the children of `parent`, which are all flow content.
+    fn blocks(&mut self, parent: &Reader) -> Result<Vec<Block>, JsonError> {
+        let mut out = Vec::new();
+        for child in try!(try!(parent.field("children")).items()) {
+            if let Some(block) = try!(self.block(&child)) {
+                out.push(block);
+            }
+        }
+        Ok(out)
+    }
+
+    fn block(&mut self, reader: &Reader) -> Result<Option<Block>, JsonError> {
+        let node_type = try!(try!(reader.field("type")).str());
+        let block = match node_type {
+            "paragraph" => Block::Paragraph {
+                children: try!(self.inlines(reader)),
+                pos: None,
+            },
+            "heading" => Block::Heading {
+                level: cmp::max(1, cmp::min(6, try!(try!(reader.field("depth")).i32()))),
+                children: try!(self.inlines(reader)),
+                pos: None,
+            },
+            "thematicBreak" => Block::Hrule { pos: None },
+            "blockquote" => Block::BlockQuote {
+                children: try!(self.blocks(reader)),
+                pos: None,
+            },
+            "list" => {
+                let ordered = match reader.optional("ordered") {
+                    Some(ordered) => try!(ordered.bool()),
+                    None => false,
+                };
+                let mut spread = try!(is_spread(reader));
+                let mut children = Vec::new();
+                for item in try!(try!(reader.field("children")).items()) {
+                    if try!(try!(item.field("type")).str()) != "listItem" {
+                        return item.error("expected a listItem node");
+                    }
+                    spread |= try!(is_spread(&item));
+                    children.push(Block::Item {
+                        children: try!(self.blocks(&item)),
+                        pos: None,
+                    });
+                }
+                let start = match reader.optional("start") {
+                    Some(start) => try!(start.i32()),
+                    None => 1,
+                };
+                Block::List {
+                    list_type: if ordered { ListType::Ordered } else { ListType::Bullet },
+                    delim: if ordered { DelimType::Period } else { DelimType::NoDelim },
+                    start: if ordered { start } else { 0 },
+                    tight: !spread,
+                    children: children,
+                    pos: None,
+                }
+            }
+            "code" => {
+                let mut info = String::new();
+                for key in &["lang", "meta"] {
+                    if let Some(part) = reader.optional(key) {
+                        if !info.is_empty() {
+                            info.push(' ');
+                        }
+                        info.push_str(try!(part.str()));
+                    }
+                }
+                let mut literal = try!(try!(reader.field("value")).str()).to_string();
+                if !literal.is_empty() {
+                    literal.push('\n');
+                }
+                Block::CodeBlock { info: info, literal: literal, pos: None }
+            }
+            "html" => {
+                let mut literal = try!(try!(reader.field("value")).str()).to_string();
+                literal.push('\n');
+                Block::Html { literal: literal, pos: None }
+            }
+            _ => {
+                self.dropped.push(reader.dropped(node_type));
+                return Ok(None);
+            }
+        };
+        Ok(Some(block))
+    }
+
+    /// Convert the children of `parent`, which are all phrasing content.
+    fn inlines(&mut self, parent: &Reader) -> Result<Vec<Inline>, JsonError> {
+        let mut out = Vec::new();
+        for child in try!(try!(parent.field("children")).items()) {
+            try!(self.inline(&child, &mut out));
+        }
+        Ok(out)
+    }
+
+    fn inline(&mut self, reader: &Reader, out: &mut Vec<Inline>) -> Result<(), JsonError> {
+        let node_type = try!(try!(reader.field("type")).str());
+        let value = || reader.field("value").and_then(|value| value.str().map(|value| value.to_string()));
+        let inline = match node_type {
+            "text" => {
+                for (index, line) in try!(value()).split('\n').enumerate() {
+                    if index > 0 {
+                        out.push(Inline::SoftBreak);
+                    }
+                    if !line.is_empty() {
+                        json::push_text(out, line);
+                    }
+                }
+                return Ok(());
+            }
+            "break" => Inline::LineBreak,
+            "inlineCode" => Inline::Code(try!(value())),
+            "html" => Inline::InlineHtml(try!(value())),
+            "emphasis" => Inline::Emph(try!(self.inlines(reader))),
+            "strong" => Inline::Strong(try!(self.inlines(reader))),
+            "link" | "image" => {
+                let url = try!(try!(reader.field("url")).str()).to_string();
+                let title = match reader.optional("title") {
+                    Some(title) => try!(title.str()).to_string(),
+                    None => String::new(),
+                };
+                if node_type == "link" {
+                    Inline::Link { url: url, title: title, children: try!(self.inlines(reader)) }
+                }
+                else {
+                    let alt = match reader.optional("alt") {
+                        Some(alt) => vec![Inline::Text(try!(alt.str()).to_string())],
+                        None => Vec::new(),
+                    };
+                    Inline::Image { url: url, title: title, children: alt }
+                }
+            }
+            "delete" | "linkReference" => {
+                self.dropped.push(reader.dropped(node_type));
+                json::extend_inlines(out, try!(self.inlines(reader)));
+                return Ok(());
+            }
+            _ => {
+                self.dropped.push(reader.dropped(node_type));
+                return Ok(());
+            }
+        };
+        out.push(inline);
+        Ok(())
+    }
+}
+
+fn is_spread(reader: &Reader) -> Result<bool, JsonError> {
+    match reader.optional("spread") {
+        Some(spread) => spread.bool(),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, render_html, MAX_DEPTH, DEFAULT};
+
+    #[test]
+    fn test_render() {
+        let source = "- a\n  b\n\n- ![*c*](/c \"C\")\n\n```js  {1} x\nf()\n```\n";
+        let doc = parse_document(source, DEFAULT);
+        let json = render_mdast(&doc).unwrap();
+        assert!(json.starts_with("{\"type\":\"root\",\"children\":[{\"type\":\"list\",\
+                                  \"ordered\":false,\"start\":null,\"spread\":true,\
+                                  \"children\":[{\"type\":\"listItem\",\"spread\":true,\
+                                  \"children\":[{\"type\":\"paragraph\",\
+                                  \"children\":[{\"type\":\"text\",\"value\":\"a\\nb\"}],\
+                                  \"position\":{\"start\":{\"line\":1,\"column\":3},\
+                                  \"end\":{\"line\":2,\"column\":4}}}]"));
+        assert!(json.contains("{\"type\":\"image\",\"url\":\"/c\",\"title\":\"C\",\"alt\":\"c\"}"));
+        assert!(json.contains("{\"type\":\"code\",\"lang\":\"js\",\"meta\":\"{1} x\",\"value\":\"f()\""));
+
+        let (back, dropped) = parse_mdast(&json).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(render_html(&back, DEFAULT), render_html(&doc, DEFAULT));
+
+        let item = doc.first_child().unwrap().first_child().unwrap();
+        assert!(render_mdast(&item).unwrap().starts_with("{\"type\":\"listItem\",\"spread\":true,"));
+    }
+
+    #[test]
+    fn test_import() {
+        let json = "{\"type\":\"root\",\"children\":[\
+                    {\"type\":\"list\",\"ordered\":true,\"start\":3,\"children\":[\
+                    {\"type\":\"listItem\",\"children\":[{\"type\":\"paragraph\",\"children\":[\
+                    {\"type\":\"delete\",\"children\":[{\"type\":\"text\",\"value\":\"x\"}]},\
+                    {\"type\":\"footnoteReference\",\"identifier\":\"1\"},\
+                    {\"type\":\"html\",\"value\":\"<br>\"}]}]}]},\
+                    {\"type\":\"table\",\"children\":[]}]}";
+        let (doc, dropped) = parse_mdast(json).unwrap();
+        assert_eq!(render_html(&doc, DEFAULT), "<ol start=\"3\">\n<li>x<br></li>\n</ol>\n");
+
+        let dropped: Vec<_> = dropped.iter().map(|d| (&d.path[..], &d.element[..])).collect();
+        assert_eq!(dropped, vec![("children[0].children[0].children[0].children[0]", "delete"),
+                                 ("children[0].children[0].children[0].children[1]", "footnoteReference"),
+                                 ("children[1]", "table")]);
+
+        let err = parse_mdast("{\"type\":\"paragraph\",\"children\":[]}").unwrap_err();
+        assert_eq!(err.to_string(), "expected a root node");
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let doc = parse_document(&"> ".repeat(50000), DEFAULT);
+        assert_eq!(render_mdast(&doc),
+                   Err(NodeError::TooDeep { node_type: NodeType::BlockQuote, limit: MAX_DEPTH }));
+
+        let doc = parse_document(&"> ".repeat(100), DEFAULT);
+        let (back, _) = parse_mdast(&render_mdast(&doc).unwrap()).unwrap();
+        assert_eq!(render_html(&back, DEFAULT), render_html(&doc, DEFAULT));
+    }
+}
@@ -1,44 +1,232 @@
-use {raw, NodeType, ListType, DelimType, NodeIterator};
-use util::Binding;
+use {raw, NodeType, NodeKind, ListType, DelimType, NodeIterator, NodeError};
+use data;
+use dump;
+use util::{self, Binding};
 
 use std::fmt;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
 use std::ptr;
 
 macro_rules! node_getter {
-    ($prop:ident : $prop_type:ty => $($node_type:path),*) => {
-        pub fn $prop(&self) -> $prop_type {
+    // Strings returned by libcmark are borrowed from the node, so they are
+    // converted separately from the `Binding` types.
+    ($(#[$attr:meta])* $prop:ident, $try_prop:ident : &str => $($node_type:path),*) => {
+        /// Like the panicking accessor of the same name, but returns a
+        /// `NodeError` if the property does not apply to this node's type.
+        pub fn $try_prop(&self) -> Result<&str, NodeError> {
             match self.node_type() {
                 $($node_type => (),)*
-                _ => panic!("{} nodes do not have property {}", self.type_string(), stringify!($prop)),
+                node_type => return Err(NodeError::InvalidProperty {
+                    node_type: node_type,
+                    property: stringify!($prop),
+                }),
             }
 
             unsafe {
                 use raw::*;
                 let raw_fn = concat_idents!(cmark_node_get_, $prop);
-                Binding::from_raw(raw_fn(self.raw))
+                Ok(util::str_from_raw(raw_fn(self.raw)))
+            }
+        }
+
+        $(#[$attr])*
+        pub fn $prop(&self) -> &str {
+            match self.$try_prop() {
+                Ok(value) => value,
+                Err(err) => panic!("{}", err),
+            }
+        }
+    };
+    ($(#[$attr:meta])* $prop:ident, $try_prop:ident : $prop_type:ty => $($node_type:path),*) => {
+        /// Like the panicking accessor of the same name, but returns a
+        /// `NodeError` if the property does not apply to this node's type.
+        pub fn $try_prop(&self) -> Result<$prop_type, NodeError> {
+            match self.node_type() {
+                $($node_type => (),)*
+                node_type => return Err(NodeError::InvalidProperty {
+                    node_type: node_type,
+                    property: stringify!($prop),
+                }),
+            }
+
+            unsafe {
+                use raw::*;
+                let raw_fn = concat_idents!(cmark_node_get_, $prop);
+                Ok(Binding::from_raw(raw_fn(self.raw)))
+            }
+        }
+
+        $(#[$attr])*
+        pub fn $prop(&self) -> $prop_type {
+            match self.$try_prop() {
+                Ok(value) => value,
+                Err(err) => panic!("{}", err),
             }
         }
     };
-    ($prop:ident : $prop_type:ty) => {
+    ($(#[$attr:meta])* $prop:ident : &str) => {
+        $(#[$attr])*
+        pub fn $prop(&self) -> &str {
+            unsafe {
+                use raw::*;
+                let raw_fn = concat_idents!(cmark_node_get_, $prop);
+                util::str_from_raw(raw_fn(self.raw))
+            }
+        }
+    };
+    ($(#[$attr:meta])* $prop:ident : $prop_type:ty) => {
+        $(#[$attr])*
         pub fn $prop(&self) -> $prop_type {
             unsafe {
                 use raw::*;
                 let raw_fn = concat_idents!(cmark_node_get_, $prop);
                 Binding::from_raw(raw_fn(self.raw))
             }
-        } 
+        }
     };
 }
 
 macro_rules! node_setter {
-    // TODO: need to use setter name instead of property name
-    // See https://github.com/rust-lang/rust/issues/12249
-    ($setter:ident : $prop_type:ty => $($node_type:path),*) => {
-        pub fn $setter(&mut self, value: $prop_type) -> bool {
-            unsafe {
+    // `concat_idents!` cannot build the setter's name from the property's
+    // (https://github.com/rust-lang/rust/issues/12249), so both are given.
+    // Errors report `$prop`, the same name the getter reports.
+
+    // String values are copied into a NUL-terminated buffer that is kept
+    // alive until libcmark has made its own copy.
+    ($(#[$attr:meta])* $setter:ident, $prop:ident : &str => $($node_type:path),*) => {
+        $(#[$attr])*
+        pub fn $setter(&mut self, value: &str) -> Result<(), NodeError> {
+            let node_type = self.node_type();
+            match node_type {
+                $($node_type => (),)*
+                _ => return Err(NodeError::InvalidProperty {
+                    node_type: node_type,
+                    property: stringify!($prop),
+                }),
+            }
+
+            let value = try!(util::to_c_string(value).map_err(|err| {
+                NodeError::InteriorNul {
+                    node_type: node_type,
+                    property: stringify!($prop),
+                    position: err.nul_position(),
+                }
+            }));
+            let updated: bool = unsafe {
+                use raw::*;
+                let raw_fn = concat_idents!(cmark_node_, $setter);
+                Binding::from_raw(raw_fn(self.raw, value.as_ptr()))
+            };
+            if updated {
+                Ok(())
+            }
+            else {
+                Err(NodeError::InvalidValue {
+                    node_type: node_type,
+                    property: stringify!($prop),
+                })
+            }
+        }
+    };
+    ($(#[$attr:meta])* $setter:ident, $prop:ident : $prop_type:ty => $($node_type:path),*) => {
+        $(#[$attr])*
+        pub fn $setter(&mut self, value: $prop_type) -> Result<(), NodeError> {
+            match self.node_type() {
+                $($node_type => (),)*
+                node_type => return Err(NodeError::InvalidProperty {
+                    node_type: node_type,
+                    property: stringify!($prop),
+                }),
+            }
+
+            let updated: bool = unsafe {
                 use raw::*;
                 let raw_fn = concat_idents!(cmark_node_, $setter);
                 Binding::from_raw(raw_fn(self.raw, value.raw()))
+            };
+            if updated {
+                Ok(())
+            }
+            else {
+                Err(NodeError::InvalidValue {
+                    node_type: self.node_type(),
+                    property: stringify!($prop),
+                })
+            }
+        }
+    };
+}
+
+/// The setters and tree edits that change a node in place. They are shared
+/// by `Node` and `NodeMut`, the only two handles that give exclusive access
+/// to a node.
+macro_rules! node_mutators {
+    () => {
+        node_setter!(
+            /// Set the string contents of this node.
+            ///
+            /// # Errors
+            /// If the node type is not `CodeBlock`, `Code`, `Text`, `Html`, or
+            /// `InlineHtml`, or if `value` contains a NUL byte.
+            set_literal, literal : &str => NodeType::CodeBlock,
+                                           NodeType::Code,
+                                           NodeType::Text,
+                                           NodeType::Html,
+                                           NodeType::InlineHtml);
+
+        node_setter!(set_header_level, header_level : i32 => NodeType::Header);
+        node_setter!(set_list_type, list_type : ListType => NodeType::List);
+        node_setter!(set_list_delim, list_delim : DelimType => NodeType::List);
+        node_setter!(set_list_start, list_start : i32 => NodeType::List);
+        node_setter!(set_list_tight, list_tight : bool => NodeType::List);
+        node_setter!(set_fence_info, fence_info : &str => NodeType::CodeBlock);
+        node_setter!(set_url, url : &str => NodeType::Link, NodeType::Image);
+        node_setter!(set_title, title : &str => NodeType::Link, NodeType::Image);
+
+        /// Insert `sibling` before this node, which passes ownership of it to
+        /// the tree containing this node. If libcmark refuses, `sibling` is
+        /// handed back.
+        pub fn insert_before(&mut self, sibling: Node) -> Result<(), Node> {
+            let inserted = unsafe {
+                Binding::from_raw(raw::cmark_node_insert_before(self.raw, sibling.raw))
+            };
+            sibling.into_tree(inserted)
+        }
+
+        /// Insert `sibling` after this node, which passes ownership of it to the
+        /// tree containing this node. If libcmark refuses, `sibling` is handed
+        /// back.
+        pub fn insert_after(&mut self, sibling: Node) -> Result<(), Node> {
+            let inserted = unsafe {
+                Binding::from_raw(raw::cmark_node_insert_after(self.raw, sibling.raw))
+            };
+            sibling.into_tree(inserted)
+        }
+
+        /// Add `child` as the first child of this node, which passes ownership
+        /// of it to this node's tree. If libcmark refuses, `child` is handed
+        /// back.
+        pub fn prepend_child(&mut self, child: Node) -> Result<(), Node> {
+            let inserted = unsafe {
+                Binding::from_raw(raw::cmark_node_prepend_child(self.raw, child.raw))
+            };
+            child.into_tree(inserted)
+        }
+
+        /// Add `child` as the last child of this node, which passes ownership of
+        /// it to this node's tree. If libcmark refuses, `child` is handed back.
+        pub fn append_child(&mut self, child: Node) -> Result<(), Node> {
+            let inserted = unsafe {
+                Binding::from_raw(raw::cmark_node_append_child(self.raw, child.raw))
+            };
+            child.into_tree(inserted)
+        }
+
+        pub fn consolidate_text_nodes(&mut self) {
+            unsafe {
+                raw::cmark_consolidate_text_nodes(self.raw)
             }
         }
     };
@@ -48,9 +236,29 @@ macro_rules! node_setter {
 /// links, and various other types. Nodes have a variety of properties depending
 /// on their type, all of which are exposed here. However, attempting to access
 /// a property that does not apply to the node's type will result in a `panic`.
-/// Setters return `true` or `false` to indicate whether the node was updated
-/// successfully.
-pub struct Node { raw: *mut raw::cmark_node,owned: bool,
+/// Each getter has a `try_` counterpart that returns a `NodeError` instead.
+/// Setters check the node type the same way and return a `NodeError` if the
+/// property does not apply or libcmark rejects the value.
+///
+/// Traversal methods such as `parent` and `first_child` return `NodeRef`
+/// handles that borrow from this node, so they cannot outlive the tree they
+/// point into. `first_child_mut` and `last_child_mut` return `NodeMut`
+/// handles for editing the nodes below this one. Inserting an owned node into
+/// a tree with `append_child` and friends moves it into that tree, so it can
+/// only be reached through the tree afterwards.
+///
+/// ```compile_fail
+/// use rcmark::{Node, NodeType};
+///
+/// let mut paragraph = Node::new(NodeType::Paragraph);
+/// let text = Node::new(NodeType::Text);
+/// paragraph.append_child(text).unwrap();
+/// drop(paragraph);
+/// text.node_type();
+/// ```
+pub struct Node {
+    raw: *mut raw::cmark_node,
+    owned: bool,
 }
 
 impl Node {
@@ -108,7 +316,7 @@ impl Node {
     ///     None       => (),
     /// }
     /// ```
-    pub fn next(&self) -> Option<Node> {
+    pub fn next(&self) -> Option<NodeRef> {
         unsafe {
             Binding::from_raw(raw::cmark_node_next(self.raw))
         }
@@ -116,7 +324,7 @@ impl Node {
 
     /// Get the previous node in the sequence from this one, or `None` if there
     /// is no prior node.
-    pub fn previous(&self) -> Option<Node> {
+    pub fn previous(&self) -> Option<NodeRef> {
         unsafe {
             Binding::from_raw(raw::cmark_node_previous(self.raw))
         }
@@ -124,21 +332,37 @@ impl Node {
 
     /// Get the parent node that contains this node, or `None` if this node does
     /// not have a parent.
-    pub fn parent(&self) -> Option<Node> {
+    pub fn parent(&self) -> Option<NodeRef> {
         unsafe {
             Binding::from_raw(raw::cmark_node_parent(self.raw))
         }
     }
 
     /// Get the first child of this node, or `None` if this node has no children.
-    pub fn first_child(&self) -> Option<Node> {
+    pub fn first_child(&self) -> Option<NodeRef> {
         unsafe {
             Binding::from_raw(raw::cmark_node_first_child(self.raw))
         }
     }
 
     /// Get the last child of this node, or `None` if this node has no children.
-    pub fn last_child(&self) -> Option<Node> {
+    pub fn last_child(&self) -> Option<NodeRef> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_last_child(self.raw))
+        }
+    }
+
+    /// Get a handle for editing the first child of this node, or `None` if
+    /// this node has no children.
+    pub fn first_child_mut(&mut self) -> Option<NodeMut> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_first_child(self.raw))
+        }
+    }
+
+    /// Get a handle for editing the last child of this node, or `None` if
+    /// this node has no children.
+    pub fn last_child_mut(&mut self) -> Option<NodeMut> {
         unsafe {
             Binding::from_raw(raw::cmark_node_last_child(self.raw))
         }
@@ -165,137 +389,390 @@ impl Node {
         }
     }
 
-    /// Get a string representation of the type of this node.
+    /// Get a typed view of this node, which only exposes the properties that
+    /// apply to its type.
     ///
     /// # Examples
     ///
     /// ```
-    /// use rcmark::{Node, NodeType};
+    /// use rcmark::{Node, NodeKind, NodeType};
     ///
-    /// let my_node = Node::new(NodeType::List);
-    /// assert_eq!(my_node.type_string(), "list");
+    /// let mut link = Node::new(NodeType::Link);
+    /// link.set_url("http://commonmark.org").unwrap();
+    /// if let NodeKind::Link(view) = link.kind() {
+    ///     assert_eq!(view.url(), "http://commonmark.org");
+    /// }
     /// ```
-    node_getter!(type_string : &str);
+    pub fn kind(&self) -> NodeKind {
+        NodeKind::of(self)
+    }
 
-    /// Get the string contents of this node.
-    ///
-    /// # Panics
-    /// If the node type is not `Code`, `Text`, `Html`, or `InnerHtml`.
-    node_getter!(literal : &str => NodeType::CodeBlock,
-                 NodeType::Code,
-                 NodeType::Text,
-                 NodeType::Html,
-                 NodeType::InlineHtml);
-
-    /// Set the string contents of this node.
-    ///
-    /// # Panics
-    /// If the node type is not `Code`, `Text`, `Html`, or `InnerHtml`.
-    node_setter!(set_literal : &str => NodeType::CodeBlock,
-                 NodeType::Code,
-                 NodeType::Text,
-                 NodeType::Html,
-                 NodeType::InlineHtml);
+    node_getter!(
+        /// Get a string representation of the type of this node.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// use rcmark::{Node, NodeType};
+        ///
+        /// let my_node = Node::new(NodeType::List);
+        /// assert_eq!(my_node.type_string(), "list");
+        /// ```
+        type_string : &str);
+
+    node_getter!(
+        /// Get the string contents of this node.
+        ///
+        /// # Panics
+        /// If the node type is not `CodeBlock`, `Code`, `Text`, `Html`, or
+        /// `InlineHtml`.
+        literal, try_literal : &str => NodeType::CodeBlock,
+                                       NodeType::Code,
+                                       NodeType::Text,
+                                       NodeType::Html,
+                                       NodeType::InlineHtml);
 
-    node_getter!(header_level : i32 => NodeType::Header);
-    node_setter!(set_header_level : i32 => NodeType::Header);
+    node_getter!(header_level, try_header_level : i32 => NodeType::Header);
 
-    node_getter!(list_type : ListType => NodeType::List);
-    node_setter!(set_list_type : ListType => NodeType::List);
+    node_getter!(list_type, try_list_type : ListType => NodeType::List);
 
-    node_getter!(list_delim : DelimType => NodeType::List);
-    node_setter!(set_list_delim : DelimType => NodeType::List);
+    node_getter!(list_delim, try_list_delim : DelimType => NodeType::List);
 
-    node_getter!(list_start : i32 => NodeType::List);
-    node_setter!(set_list_start : i32 => NodeType::List);
+    node_getter!(list_start, try_list_start : i32 => NodeType::List);
 
-    node_getter!(list_tight : bool => NodeType::List);
-    node_setter!(set_list_tight : bool => NodeType::List);
+    node_getter!(list_tight, try_list_tight : bool => NodeType::List);
 
-    node_getter!(fence_info : &str => NodeType::CodeBlock);
-    node_setter!(set_fence_info : &str => NodeType::CodeBlock);
+    node_getter!(fence_info, try_fence_info : &str => NodeType::CodeBlock);
 
-    node_getter!(url : &str => NodeType::Link, NodeType::Image);
-    node_setter!(set_url : &str => NodeType::Link, NodeType::Image);
+    node_getter!(url, try_url : &str => NodeType::Link, NodeType::Image);
 
-    node_getter!(title : &str => NodeType::Link, NodeType::Image);
-    node_setter!(set_title : &str => NodeType::Link, NodeType::Image);
+    node_getter!(title, try_title : &str => NodeType::Link, NodeType::Image);
 
     node_getter!(start_line : i32);
     node_getter!(start_column : i32);
     node_getter!(end_column: i32);
     node_getter!(end_line : i32);
 
+    node_mutators!();
+
     pub fn unlink(&mut self) {
         unsafe {
             raw::cmark_node_unlink(self.raw)
         }
     }
 
-    pub fn insert_before(&mut self, sibling: &mut Node) -> bool {
+    /// Give up this handle once libcmark has `inserted` the node into a tree,
+    /// which owns it from then on. Otherwise hand the node back.
+    fn into_tree(self, inserted: bool) -> Result<(), Node> {
+        if inserted {
+            mem::forget(self);
+            Ok(())
+        }
+        else {
+            Err(self)
+        }
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        if self.owned {
+            data::free_subtree(self);
+            unsafe { raw::cmark_node_free(self.raw) }
+        }
+    }
+}
+
+/// The alternate form, `{:#?}`, prints the whole subtree as with `dump`.
+impl fmt::Debug for Node {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if fmt.alternate() {
+            dump::write_tree(self, fmt)
+        }
+        else {
+            write!(fmt, "Node[raw = {:p}]", self.raw)
+        }
+    }
+}
+
+impl Binding for Node {
+    type Raw = *mut raw::cmark_node;
+
+    unsafe fn from_raw(raw: *mut raw::cmark_node) -> Node {
+        Node::from_raw(raw, false)
+    }
+
+    fn raw(&self) -> *mut raw::cmark_node { self.raw }
+}
+
+/// A borrowed handle to a node inside a tree owned by someone else, usually a
+/// `Document`. The `'a` lifetime ties the handle to its owner, so the compiler
+/// rejects any use of the handle after the tree has been freed.
+///
+/// `NodeRef` dereferences to `Node`, so every property is available on it,
+/// but it cannot change the tree: handles can be cloned freely, and any of
+/// them may be looking at a node that an edit would free. Edits go through a
+/// `NodeMut` instead. Its own traversal methods return handles with the same
+/// `'a` lifetime rather than one bound to the handle itself.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, NodeType, DEFAULT};
+///
+/// let doc = parse_document("# Hello", DEFAULT);
+/// let text = {
+///     let header = doc.root().first_child().unwrap();
+///     header.first_child().unwrap()
+/// };
+/// assert_eq!(text.node_type(), NodeType::Text);
+/// assert_eq!(text.parent().unwrap().node_type(), NodeType::Header);
+/// ```
+pub struct NodeRef<'a> {
+    node: Node,
+    marker: PhantomData<&'a Node>,
+}
+
+impl<'a> NodeRef<'a> {
+    /// Wrap a raw `cmark_node` owned by a tree that lives for at least `'a`.
+    pub unsafe fn from_raw(raw: *mut raw::cmark_node) -> NodeRef<'a> {
+        NodeRef {
+            node: Node::from_raw(raw, false),
+            marker: PhantomData,
+        }
+    }
+
+    /// Get the next node in sequence after this one, or `None` if there is not
+    /// a subsequent node.
+    pub fn next(&self) -> Option<NodeRef<'a>> {
         unsafe {
-            Binding::from_raw(raw::cmark_node_insert_before(self.raw, sibling.raw))
+            Binding::from_raw(raw::cmark_node_next(self.node.raw))
         }
     }
 
-    pub fn insert_after(&mut self, sibling: &mut Node) -> bool {
+    /// Get the previous node in the sequence from this one, or `None` if there
+    /// is no prior node.
+    pub fn previous(&self) -> Option<NodeRef<'a>> {
         unsafe {
-            Binding::from_raw(raw::cmark_node_insert_after(self.raw, sibling.raw))
+            Binding::from_raw(raw::cmark_node_previous(self.node.raw))
         }
     }
 
-    pub fn prepend_child(&mut self, child: &mut Node) -> bool {
+    /// Get the parent node that contains this node, or `None` if this node does
+    /// not have a parent.
+    pub fn parent(&self) -> Option<NodeRef<'a>> {
         unsafe {
-            Binding::from_raw(raw::cmark_node_prepend_child(self.raw, child.raw))
+            Binding::from_raw(raw::cmark_node_parent(self.node.raw))
         }
     }
 
-    pub fn append_child(&mut self, child: &mut Node) -> bool {
+    /// Get the first child of this node, or `None` if this node has no children.
+    pub fn first_child(&self) -> Option<NodeRef<'a>> {
         unsafe {
-            Binding::from_raw(raw::cmark_node_append_child(self.raw, child.raw))
+            Binding::from_raw(raw::cmark_node_first_child(self.node.raw))
         }
     }
 
-    pub fn consolidate_text_nodes(&mut self) {
+    /// Get the last child of this node, or `None` if this node has no children.
+    pub fn last_child(&self) -> Option<NodeRef<'a>> {
         unsafe {
-            raw::cmark_consolidate_text_nodes(self.raw)
+            Binding::from_raw(raw::cmark_node_last_child(self.node.raw))
         }
     }
-}
 
-impl Drop for Node {
-    fn drop(&mut self) {
-        if self.owned {
-            unsafe { raw::cmark_node_free(self.raw) }
+    /// Create a new iterator over this node and its children.
+    pub fn iter(&self) -> NodeIterator<'a> {
+        unsafe {
+            NodeIterator::from_raw(raw::cmark_iter_new(self.node.raw))
         }
     }
 }
 
-impl fmt::Debug for Node {
+/// Borrow a node as a handle whose lifetime is tied to the borrow of the node
+/// itself, so that `Node` methods can share the `NodeRef` implementations.
+pub fn handle(node: &Node) -> NodeRef {
+    unsafe { NodeRef::from_raw(node.raw) }
+}
+
+impl<'a> Deref for NodeRef<'a> {
+    type Target = Node;
+
+    fn deref(&self) -> &Node { &self.node }
+}
+
+impl<'a> Clone for NodeRef<'a> {
+    fn clone(&self) -> NodeRef<'a> {
+        unsafe { NodeRef::from_raw(self.node.raw) }
+    }
+}
+
+impl<'a> fmt::Debug for NodeRef<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(fmt, "Node[raw = {:p}]", self.raw)
+        fmt::Debug::fmt(&self.node, fmt)
     }
 }
 
-impl Binding for Node {
+impl<'a> Binding for Option<NodeRef<'a>> {
     type Raw = *mut raw::cmark_node;
 
-    unsafe fn from_raw(raw: *mut raw::cmark_node) -> Node {
-        Node::from_raw(raw, false)
+    unsafe fn from_raw(raw: *mut raw::cmark_node) -> Option<NodeRef<'a>> {
+        if raw.is_null() {
+            None
+        }
+        else {
+            Some(NodeRef::from_raw(raw))
+        }
     }
 
-    fn raw(&self) -> *mut raw::cmark_node { self.raw }
+    fn raw(&self) -> *mut raw::cmark_node {
+        match *self {
+            Some(ref node) => node.raw(),
+            None       => ptr::null_mut(),
+        }
+    }
 }
 
-impl Binding for Option<Node> {
+/// An exclusive handle to a node inside a tree owned by someone else, for
+/// editing it. It is obtained from a mutable borrow of the owner, such as
+/// with `Document::root_mut` or `Node::first_child_mut`, so while it exists
+/// no other handle into the tree can be used.
+///
+/// `NodeMut` dereferences to `Node` for reading properties, and has the
+/// setters and tree edits of `Node` as its own methods, except that `unlink`
+/// is replaced by `detach`, which takes ownership of the unlinked node rather
+/// than leaking it. It cannot be cloned.
+/// A handle only ever moves down or across the tree: `first_child_mut`
+/// reborrows it to reach a child, and `into_next` and friends consume it to
+/// reach a sibling. Edits that detach the node itself, such as `detach`,
+/// consume the handle. Together these mean an edit can only free nodes below
+/// every other live handle, never one that a handle still points to.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, render_html, DEFAULT};
+///
+/// let mut doc = parse_document("# Hello\n\nWorld", DEFAULT);
+/// {
+///     let mut root = doc.root_mut();
+///     let mut header = root.first_child_mut().unwrap();
+///     header.set_header_level(2).unwrap();
+///     let mut paragraph = header.into_next().unwrap();
+///     paragraph.first_child_mut().unwrap().set_literal("there").unwrap();
+/// }
+/// assert_eq!(render_html(&doc, DEFAULT), "<h2>Hello</h2>\n<p>there</p>\n");
+/// ```
+///
+/// A `NodeRef` cannot be held across an edit:
+///
+/// ```compile_fail
+/// use rcmark::{parse_document, DEFAULT};
+///
+/// let mut doc = parse_document("Hello", DEFAULT);
+/// let paragraph = doc.first_child().unwrap();
+/// doc.root_mut().first_child_mut().unwrap().detach().unwrap();
+/// paragraph.node_type();
+/// ```
+pub struct NodeMut<'a> {
+    node: Node,
+    marker: PhantomData<&'a mut Node>,
+}
+
+impl<'a> NodeMut<'a> {
+    /// Wrap a raw `cmark_node` in a tree that is exclusively borrowed for at
+    /// least `'a`.
+    pub unsafe fn from_raw(raw: *mut raw::cmark_node) -> NodeMut<'a> {
+        NodeMut {
+            node: Node::from_raw(raw, false),
+            marker: PhantomData,
+        }
+    }
+
+    /// Get a handle for editing the first child of this node, or `None` if
+    /// this node has no children.
+    pub fn first_child_mut(&mut self) -> Option<NodeMut> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_first_child(self.node.raw))
+        }
+    }
+
+    /// Get a handle for editing the last child of this node, or `None` if
+    /// this node has no children.
+    pub fn last_child_mut(&mut self) -> Option<NodeMut> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_last_child(self.node.raw))
+        }
+    }
+
+    /// Move to the next sibling, or `None` if there is none.
+    pub fn into_next(self) -> Option<NodeMut<'a>> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_next(self.node.raw))
+        }
+    }
+
+    /// Move to the previous sibling, or `None` if there is none.
+    pub fn into_previous(self) -> Option<NodeMut<'a>> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_previous(self.node.raw))
+        }
+    }
+
+    /// Move to the first child, or `None` if this node has no children.
+    pub fn into_first_child(self) -> Option<NodeMut<'a>> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_first_child(self.node.raw))
+        }
+    }
+
+    /// Move to the last child, or `None` if this node has no children.
+    pub fn into_last_child(self) -> Option<NodeMut<'a>> {
+        unsafe {
+            Binding::from_raw(raw::cmark_node_last_child(self.node.raw))
+        }
+    }
+
+    /// Unlink this node from its tree and take ownership of it, so that it is
+    /// freed when the returned `Node` is dropped or can be inserted elsewhere.
+    ///
+    /// # Errors
+    /// If this node has no parent. Such a node is the root of the tree the
+    /// handle borrows, and stays owned by it.
+    pub fn detach(self) -> Result<Node, NodeError> {
+        if self.parent().is_none() {
+            return Err(NodeError::NoParent {
+                node_type: self.node_type(),
+            });
+        }
+        let mut node = unsafe { Node::from_raw(self.node.raw, true) };
+        node.unlink();
+        Ok(node)
+    }
+
+    node_mutators!();
+}
+
+impl<'a> Deref for NodeMut<'a> {
+    type Target = Node;
+
+    fn deref(&self) -> &Node { &self.node }
+}
+
+impl<'a> fmt::Debug for NodeMut<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        fmt::Debug::fmt(&self.node, fmt)
+    }
+}
+
+impl<'a> Binding for Option<NodeMut<'a>> {
     type Raw = *mut raw::cmark_node;
 
-    unsafe fn from_raw(raw: *mut raw::cmark_node) -> Option<Node> {
+    unsafe fn from_raw(raw: *mut raw::cmark_node) -> Option<NodeMut<'a>> {
         if raw.is_null() {
             None
         }
         else {
-            Some(Binding::from_raw(raw))
+            Some(NodeMut::from_raw(raw))
         }
     }
 
@@ -310,7 +787,7 @@ impl Binding for Option<Node> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use super::super::NodeType;
+    use super::super::{NodeType, EventType, NodeError};
 
     #[test]
     fn test_node_type() {
@@ -318,4 +795,65 @@ mod test {
         assert!(node.node_type() == NodeType::List);
         assert!(node.type_string() == "list");
     }
+
+    #[test]
+    fn test_property_errors() {
+        let mut node = Node::new(NodeType::Paragraph);
+        assert_eq!(node.try_header_level(),
+                   Err(NodeError::InvalidProperty { node_type: NodeType::Paragraph,
+                                                    property: "header_level" }));
+        assert_eq!(node.set_url("http://example.com"),
+                   Err(NodeError::InvalidProperty { node_type: NodeType::Paragraph,
+                                                    property: "url" }));
+
+        let mut header = Node::new(NodeType::Header);
+        assert_eq!(header.set_header_level(2), Ok(()));
+        assert_eq!(header.try_header_level(), Ok(2));
+        assert_eq!(header.set_header_level(9),
+                   Err(NodeError::InvalidValue { node_type: NodeType::Header,
+                                                 property: "header_level" }));
+    }
+
+    #[test]
+    fn test_set_string_property() {
+        let mut text = Node::new(NodeType::Text);
+        let literal = String::from("Hello");
+        text.set_literal(&literal).unwrap();
+        drop(literal);
+        assert_eq!(text.literal(), "Hello");
+
+        assert_eq!(text.set_literal("nul\0byte"),
+                   Err(NodeError::InteriorNul { node_type: NodeType::Text,
+                                                property: "literal",
+                                                position: 3 }));
+    }
+
+    #[test]
+    fn test_set_list_tight() {
+        let mut list = Node::new(NodeType::List);
+        list.set_list_tight(true).unwrap();
+        assert!(list.list_tight());
+        list.set_list_tight(false).unwrap();
+        assert!(!list.list_tight());
+    }
+
+    #[test]
+    fn test_append_transfers_ownership() {
+        let mut paragraph = Node::new(NodeType::Paragraph);
+        paragraph.append_child(Node::new(NodeType::Text)).unwrap();
+        assert_eq!(paragraph.first_child().unwrap().node_type(), NodeType::Text);
+
+        let refused = paragraph.append_child(Node::new(NodeType::Paragraph)).unwrap_err();
+        assert_eq!(refused.node_type(), NodeType::Paragraph);
+    }
+
+    #[test]
+    fn test_iterator_terminates() {
+        let mut paragraph = Node::new(NodeType::Paragraph);
+        paragraph.append_child(Node::new(NodeType::Text)).unwrap();
+        let events: Vec<_> = paragraph.iter().map(|(ev, node)| (ev, node.node_type())).collect();
+        assert_eq!(events, vec![(EventType::Enter, NodeType::Paragraph),
+                                (EventType::Enter, NodeType::Text),
+                                (EventType::Exit, NodeType::Paragraph)]);
+    }
 }
@@ -0,0 +1,478 @@
+use {Node, Block, Inline, Document, ListType, DelimType, NodeError};
+use json::{self, Json, JsonError, Reader, DroppedElement, push_text, extend_inlines};
+
+use std::cmp;
+
+/// The version of pandoc-types whose JSON format `render_pandoc_json` writes.
+const PANDOC_API_VERSION: [i32; 3] = [1, 23, 1];
+
+/// Render a tree as Pandoc's JSON AST, as read by `pandoc -f json`.
+///
+/// Every node type has a Pandoc counterpart: paragraphs in tight lists
+/// become `Plain`, HTML becomes `RawBlock` and `RawInline` in the `html`
+/// format, the info string of a code block becomes its classes, and text is
+/// split into `Str` and `Space` elements as Pandoc's readers do. Rendering a
+/// node other than a document gives a document containing just that node.
+/// Source positions are not written.
+///
+/// # Errors
+///
+/// Returns `NodeError::TooDeep` if the tree is nested more than `MAX_DEPTH`
+/// levels deep, as with `Block::from_node`.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, render_pandoc_json, DEFAULT};
+///
+/// let doc = parse_document("Hello *world*", DEFAULT);
+/// assert_eq!(render_pandoc_json(&doc).unwrap(),
+///            "{\"pandoc-api-version\":[1,23,1],\"meta\":{},\"blocks\":[{\"t\":\"Para\",\"c\":\
+///             [{\"t\":\"Str\",\"c\":\"Hello\"},{\"t\":\"Space\"},\
+///             {\"t\":\"Emph\",\"c\":[{\"t\":\"Str\",\"c\":\"world\"}]}]}]}");
+/// ```
+pub fn render_pandoc_json(root: &Node) -> Result<String, NodeError> {
+    let blocks = if root.node_type().is_block() {
+        match try!(Block::from_node(root)) {
+            Block::Document { ref children, .. } => write_blocks(children, false),
+            Block::Item { ref children, .. } => write_blocks(children, false),
+            ref block => vec![write_block(block, false)],
+        }
+    }
+    else {
+        let inlines = write_inlines(&[try!(Inline::from_node(root))]);
+        vec![element("Plain", Some(Json::Array(inlines)))]
+    };
+
+    let version = PANDOC_API_VERSION.iter().map(|&part| Json::Number(part as f64)).collect();
+    Ok(Json::object(vec![
+        ("pandoc-api-version", Json::Array(version)),
+        ("meta", Json::Object(Vec::new())),
+        ("blocks", Json::Array(blocks)),
+    ]).to_string())
+}
+
+/// Read a document from Pandoc's JSON AST, as written by `pandoc -t json`.
+///
+/// Pandoc can express much more than CommonMark, so only the subset that
+/// maps onto rcmark's node types is kept. Alongside the document, this
+/// returns each element that had to be dropped, such as tables, notes, math
+/// and raw content in formats other than HTML, and any document metadata.
+/// `Div`, `Span` and the other inline styles that CommonMark lacks are
+/// reported too, but their contents are kept in their place. `Plain` and
+/// `LineBlock` become paragraphs, and a list is tight if none of its items
+/// contain a `Para` directly.
+///
+/// Versions of the format older than pandoc-types 1.17, which had no
+/// `pandoc-api-version`, are not supported.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_pandoc_json, render_html, DEFAULT};
+///
+/// let json = "{\"pandoc-api-version\":[1,23,1],\"meta\":{},\"blocks\":[\
+///             {\"t\":\"Para\",\"c\":[{\"t\":\"Str\",\"c\":\"x\"},{\"t\":\"Space\"},\
+///             {\"t\":\"Math\",\"c\":[{\"t\":\"InlineMath\"},\"y^2\"]}]}]}";
+/// let (doc, dropped) = parse_pandoc_json(json).unwrap();
+/// assert_eq!(render_html(&doc, DEFAULT), "<p>x </p>\n");
+/// assert_eq!(dropped[0].element, "Math");
+/// assert_eq!(dropped[0].path, "blocks[0].c[2]");
+/// ```
+pub fn parse_pandoc_json(input: &str) -> Result<(Document, Vec<DroppedElement>), JsonError> {
+    let json = try!(json::parse(input));
+    let root = Reader::new(&json, "");
+
+    let version = try!(root.field("pandoc-api-version"));
+    let parts = try!(version.items());
+    let major = match parts.get(0) { Some(part) => try!(part.i32()), None => 0 };
+    let minor = match parts.get(1) { Some(part) => try!(part.i32()), None => 0 };
+    if major != 1 || minor < 17 {
+        return version.error("unsupported pandoc-api-version");
+    }
+
+    let mut importer = Importer { dropped: Vec::new() };
+    if let Some(meta) = root.optional("meta") {
+        if *meta.json != Json::Object(Vec::new()) {
+            importer.dropped.push(meta.dropped("Meta"));
+        }
+    }
+    let blocks = try!(root.field("blocks"));
+    let children = try!(importer.blocks(&blocks)).0;
+    let doc = Block::Document { children: children, pos: None };
+    match doc.to_document() {
+        Ok(doc) => Ok((doc, importer.dropped)),
+        Err(err) => blocks.error(&err.to_string()),
+    }
+}
+
+fn element(name: &str, content: Option<Json>) -> Json {
+    let mut members = vec![("t", Json::string(name))];
+    members.extend(content.map(|content| ("c", content)));
+    Json::object(members)
+}
+
+/// An empty `Attr`: identifier, classes and key-value pairs.
+fn attr(classes: Vec<Json>) -> Json {
+    Json::Array(vec![Json::string(""), Json::Array(classes), Json::Array(Vec::new())])
+}
+
+fn write_blocks(blocks: &[Block], tight: bool) -> Vec<Json> {
+    blocks.iter().map(|block| write_block(block, tight)).collect()
+}
+
+fn write_items(items: &[Block], tight: bool) -> Json {
+    Json::Array(items.iter().map(|item| match *item {
+        Block::Item { ref children, .. } => Json::Array(write_blocks(children, tight)),
+        ref block => Json::Array(vec![write_block(block, tight)]),
+    }).collect())
+}
+
+fn write_block(block: &Block, tight: bool) -> Json {
+    match *block {
+        Block::Document { ref children, .. } | Block::Item { ref children, .. } =>
+            element("Div", Some(Json::Array(vec![attr(Vec::new()),
+                                                 Json::Array(write_blocks(children, false))]))),
+        Block::BlockQuote { ref children, .. } =>
+            element("BlockQuote", Some(Json::Array(write_blocks(children, false)))),
+        Block::List { list_type: ListType::Ordered, delim, start, tight, ref children, .. } => {
+            let delim = if delim == DelimType::Paren { "OneParen" } else { "Period" };
+            let attributes = Json::Array(vec![
+                Json::Number(start as f64),
+                element("Decimal", None),
+                element(delim, None),
+            ]);
+            element("OrderedList", Some(Json::Array(vec![attributes, write_items(children, tight)])))
+        }
+        Block::List { tight, ref children, .. } =>
+            element("BulletList", Some(write_items(children, tight))),
+        Block::CodeBlock { ref info, ref literal, .. } => {
+            let classes = info.split_whitespace().map(Json::string).collect();
+            let literal = if literal.ends_with('\n') { &literal[..literal.len() - 1] } else { &literal[..] };
+            element("CodeBlock", Some(Json::Array(vec![attr(classes), Json::string(literal)])))
+        }
+        Block::Html { ref literal, .. } =>
+            element("RawBlock", Some(Json::Array(vec![Json::string("html"), Json::string(literal)]))),
+        Block::Paragraph { ref children, .. } =>
+            element(if tight { "Plain" } else { "Para" }, Some(Json::Array(write_inlines(children)))),
+        Block::Heading { level, ref children, .. } =>
+            element("Header", Some(Json::Array(vec![
+                Json::Number(level as f64),
+                attr(Vec::new()),
+                Json::Array(write_inlines(children)),
+            ]))),
+        Block::Hrule { .. } => element("HorizontalRule", None),
+    }
+}
+
+fn write_inlines(inlines: &[Inline]) -> Vec<Json> {
+    let mut out = Vec::new();
+    for inline in inlines {
+        match *inline {
+            Inline::Text(ref literal) => {
+                for (index, word) in literal.split(' ').enumerate() {
+                    if index > 0 {
+                        out.push(element("Space", None));
+                    }
+                    if !word.is_empty() {
+                        out.push(element("Str", Some(Json::string(word))));
+                    }
+                }
+            }
+            Inline::SoftBreak => out.push(element("SoftBreak", None)),
+            Inline::LineBreak => out.push(element("LineBreak", None)),
+            Inline::Code(ref literal) =>
+                out.push(element("Code", Some(Json::Array(vec![attr(Vec::new()), Json::string(literal)])))),
+            Inline::InlineHtml(ref literal) =>
+                out.push(element("RawInline", Some(Json::Array(vec![Json::string("html"),
+                                                                    Json::string(literal)])))),
+            Inline::Emph(ref children) =>
+                out.push(element("Emph", Some(Json::Array(write_inlines(children))))),
+            Inline::Strong(ref children) =>
+                out.push(element("Strong", Some(Json::Array(write_inlines(children))))),
+            Inline::Link { ref url, ref title, ref children } |
+            Inline::Image { ref url, ref title, ref children } => {
+                let name = if let Inline::Link { .. } = *inline { "Link" } else { "Image" };
+                out.push(element(name, Some(Json::Array(vec![
+                    attr(Vec::new()),
+                    Json::Array(write_inlines(children)),
+                    Json::Array(vec![Json::string(url), Json::string(title)]),
+                ]))));
+            }
+        }
+    }
+    out
+}
+
+struct Importer {
+    dropped: Vec<DroppedElement>,
+}
+
+impl Importer {
+    /// Convert an array of blocks, and say whether any of them was a `Para`.
+    fn blocks(&mut self, reader: &Reader) -> Result<(Vec<Block>, bool), JsonError> {
+        let mut out = Vec::new();
+        let mut para = false;
+        for item in try!(reader.items()) {
+            para |= try!(self.block(&item, &mut out));
+        }
+        Ok((out, para))
+    }
+
+    fn block(&mut self, reader: &Reader, out: &mut Vec<Block>) -> Result<bool, JsonError> {
+        let name = try!(try!(reader.field("t")).str());
+        let block = match name {
+            "Plain" | "Para" => Block::Paragraph {
+                children: try!(self.inlines(&try!(reader.field("c")))),
+                pos: None,
+            },
+            "LineBlock" => {
+                let mut children = Vec::new();
+                for (index, line) in try!(try!(reader.field("c")).items()).iter().enumerate() {
+                    if index > 0 {
+                        children.push(Inline::LineBreak);
+                    }
+                    children.extend(try!(self.inlines(line)));
+                }
+                Block::Paragraph { children: children, pos: None }
+            }
+            "CodeBlock" => {
+                let content = try!(try!(reader.field("c")).tuple(2));
+                let attr = try!(content[0].tuple(3));
+                let mut classes = Vec::new();
+                for class in try!(attr[1].items()) {
+                    classes.push(try!(class.str()));
+                }
+                let mut literal = try!(content[1].str()).to_string();
+                if !literal.is_empty() && !literal.ends_with('\n') {
+                    literal.push('\n');
+                }
+                Block::CodeBlock { info: classes.join(" "), literal: literal, pos: None }
+            }
+            "RawBlock" => {
+                let content = try!(try!(reader.field("c")).tuple(2));
+                if !is_html(try!(content[0].str())) {
+                    self.dropped.push(reader.dropped(name));
+                    return Ok(false);
+                }
+                Block::Html { literal: try!(content[1].str()).to_string(), pos: None }
+            }
+            "BlockQuote" => Block::BlockQuote {
+                children: try!(self.blocks(&try!(reader.field("c")))).0,
+                pos: None,
+            },
+            "OrderedList" => {
+                let content = try!(try!(reader.field("c")).tuple(2));
+                let attributes = try!(content[0].tuple(3));
+                let delim = match try!(try!(attributes[2].field("t")).str()) {
+                    "OneParen" | "TwoParens" => DelimType::Paren,
+                    _ => DelimType::Period,
+                };
+                let (children, tight) = try!(self.items(&content[1]));
+                Block::List {
+                    list_type: ListType::Ordered,
+                    delim: delim,
+                    start: try!(attributes[0].i32()),
+                    tight: tight,
+                    children: children,
+                    pos: None,
+                }
+            }
+            "BulletList" => {
+                let (children, tight) = try!(self.items(&try!(reader.field("c"))));
+                Block::List {
+                    list_type: ListType::Bullet,
+                    delim: DelimType::NoDelim,
+                    start: 0,
+                    tight: tight,
+                    children: children,
+                    pos: None,
+                }
+            }
+            "Header" => {
+                let content = try!(try!(reader.field("c")).tuple(3));
+                Block::Heading {
+                    level: cmp::max(1, cmp::min(6, try!(content[0].i32()))),
+                    children: try!(self.inlines(&content[2])),
+                    pos: None,
+                }
+            }
+            "HorizontalRule" => Block::Hrule { pos: None },
+            "Div" => {
+                self.dropped.push(reader.dropped(name));
+                let content = try!(try!(reader.field("c")).tuple(2));
+                let (children, para) = try!(self.blocks(&content[1]));
+                out.extend(children);
+                return Ok(para);
+            }
+            _ => {
+                self.dropped.push(reader.dropped(name));
+                return Ok(false);
+            }
+        };
+        out.push(block);
+        Ok(name == "Para")
+    }
+
+    /// Convert the items of a list, and say whether the list is tight.
+    fn items(&mut self, reader: &Reader) -> Result<(Vec<Block>, bool), JsonError> {
+        let mut items = Vec::new();
+        let mut tight = true;
+        for item in try!(reader.items()) {
+            let (children, para) = try!(self.blocks(&item));
+            tight &= !para;
+            items.push(Block::Item { children: children, pos: None });
+        }
+        Ok((items, tight))
+    }
+
+    fn inlines(&mut self, reader: &Reader) -> Result<Vec<Inline>, JsonError> {
+        let mut out = Vec::new();
+        for item in try!(reader.items()) {
+            try!(self.inline(&item, &mut out));
+        }
+        Ok(out)
+    }
+
+    fn inline(&mut self, reader: &Reader, out: &mut Vec<Inline>) -> Result<(), JsonError> {
+        let name = try!(try!(reader.field("t")).str());
+        let inline = match name {
+            "Str" => {
+                push_text(out, try!(try!(reader.field("c")).str()));
+                return Ok(());
+            }
+            "Space" => {
+                push_text(out, " ");
+                return Ok(());
+            }
+            "SoftBreak" => Inline::SoftBreak,
+            "LineBreak" => Inline::LineBreak,
+            "Code" => {
+                let content = try!(try!(reader.field("c")).tuple(2));
+                Inline::Code(try!(content[1].str()).to_string())
+            }
+            "RawInline" => {
+                let content = try!(try!(reader.field("c")).tuple(2));
+                if !is_html(try!(content[0].str())) {
+                    self.dropped.push(reader.dropped(name));
+                    return Ok(());
+                }
+                Inline::InlineHtml(try!(content[1].str()).to_string())
+            }
+            "Emph" => Inline::Emph(try!(self.inlines(&try!(reader.field("c"))))),
+            "Strong" => Inline::Strong(try!(self.inlines(&try!(reader.field("c"))))),
+            "Link" | "Image" => {
+                let content = try!(try!(reader.field("c")).tuple(3));
+                let target = try!(content[2].tuple(2));
+                let url = try!(target[0].str()).to_string();
+                let title = try!(target[1].str()).to_string();
+                let children = try!(self.inlines(&content[1]));
+                if name == "Link" {
+                    Inline::Link { url: url, title: title, children: children }
+                }
+                else {
+                    Inline::Image { url: url, title: title, children: children }
+                }
+            }
+            "Quoted" => {
+                let content = try!(try!(reader.field("c")).tuple(2));
+                let quote = match try!(try!(content[0].field("t")).str()) {
+                    "SingleQuote" => "'",
+                    _ => "\"",
+                };
+                push_text(out, quote);
+                extend_inlines(out, try!(self.inlines(&content[1])));
+                push_text(out, quote);
+                return Ok(());
+            }
+            "Underline" | "Strikeout" | "Superscript" | "Subscript" | "SmallCaps" | "Span" | "Cite" => {
+                // Keep the contents, which are the last part of each of these.
+                self.dropped.push(reader.dropped(name));
+                let content = try!(reader.field("c"));
+                let contents = if name == "Span" || name == "Cite" {
+                    try!(content.tuple(2)).pop().unwrap()
+                }
+                else {
+                    content
+                };
+                extend_inlines(out, try!(self.inlines(&contents)));
+                return Ok(());
+            }
+            _ => {
+                self.dropped.push(reader.dropped(name));
+                return Ok(());
+            }
+        };
+        out.push(inline);
+        Ok(())
+    }
+}
+
+fn is_html(format: &str) -> bool {
+    format == "html" || format == "html4" || format == "html5"
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, render_html, NodeType, MAX_DEPTH, DEFAULT};
+
+    #[test]
+    fn test_round_trip() {
+        let source = "# Title\n\n1) *one* `two`\n2) [three](/3 \"3\")  \n   ![four](/4)\n\n\
+                      - a\n\n- b\n\n```sh  -x\nls\n```\n\n<p>five</p>\n\n> ---\n";
+        let doc = parse_document(source, DEFAULT);
+        let json = render_pandoc_json(&doc).unwrap();
+        assert!(json.contains("{\"t\":\"OrderedList\",\"c\":[[1,{\"t\":\"Decimal\"},{\"t\":\"OneParen\"}],\
+                               [[{\"t\":\"Plain\""));
+        assert!(json.contains("{\"t\":\"BulletList\",\"c\":[[{\"t\":\"Para\""));
+        assert!(json.contains("{\"t\":\"CodeBlock\",\"c\":[[\"\",[\"sh\",\"-x\"],[]],\"ls\"]}"));
+
+        let (back, dropped) = parse_pandoc_json(&json).unwrap();
+        assert!(dropped.is_empty());
+        assert_eq!(render_html(&back, DEFAULT), render_html(&doc, DEFAULT));
+    }
+
+    #[test]
+    fn test_dropped_elements() {
+        let json = "{\"pandoc-api-version\":[1,22],\"meta\":{\"title\":{\"t\":\"MetaString\",\"c\":\"T\"}},\
+                    \"blocks\":[{\"t\":\"Div\",\"c\":[[\"id\",[],[]],[\
+                    {\"t\":\"Para\",\"c\":[{\"t\":\"Span\",\"c\":[[\"\",[],[]],[{\"t\":\"Str\",\"c\":\"a\"}]]},\
+                    {\"t\":\"Quoted\",\"c\":[{\"t\":\"DoubleQuote\"},[{\"t\":\"Str\",\"c\":\"b\"}]]},\
+                    {\"t\":\"Note\",\"c\":[]}]}]]},\
+                    {\"t\":\"RawBlock\",\"c\":[\"latex\",\"\\\\newpage\"]}]}";
+        let (doc, dropped) = parse_pandoc_json(json).unwrap();
+        assert_eq!(render_html(&doc, DEFAULT), "<p>a&quot;b&quot;</p>\n");
+        assert_eq!(doc.first_child().unwrap().first_child().unwrap().node_type(), NodeType::Text);
+
+        let dropped: Vec<_> = dropped.iter().map(|d| (&d.path[..], &d.element[..])).collect();
+        assert_eq!(dropped, vec![("meta", "Meta"),
+                                 ("blocks[0]", "Div"),
+                                 ("blocks[0].c[1][0].c[0]", "Span"),
+                                 ("blocks[0].c[1][0].c[2]", "Note"),
+                                 ("blocks[1]", "RawBlock")]);
+
+        let old = "[{\"unMeta\":{}},[]]";
+        assert_eq!(parse_pandoc_json(old).unwrap_err().to_string(), "expected an object");
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let quotes = |depth| {
+            format!("{{\"pandoc-api-version\":[1,23],\"meta\":{{}},\"blocks\":[{}\
+                     {{\"t\":\"Para\",\"c\":[{{\"t\":\"Str\",\"c\":\"x\"}}]}}{}]}}",
+                    "{\"t\":\"BlockQuote\",\"c\":[".repeat(depth), "]}".repeat(depth))
+        };
+        let (doc, _) = parse_pandoc_json(&quotes(100)).unwrap();
+        assert_eq!(render_html(&doc, DEFAULT).matches("<blockquote>").count(), 100);
+
+        match parse_pandoc_json(&quotes(100000)) {
+            Err(JsonError::Syntax { .. }) => (),
+            result => panic!("expected a syntax error, got {:?}", result),
+        }
+
+        let deep = parse_document(&"> ".repeat(50000), DEFAULT);
+        assert_eq!(render_pandoc_json(&deep),
+                   Err(NodeError::TooDeep { node_type: NodeType::BlockQuote, limit: MAX_DEPTH }));
+    }
+}
@@ -1,19 +1,27 @@
 
-use {raw, Node, CmarkOptions};
+use {raw, Document, CmarkOptions, SourceMap};
 use util::Binding;
 
-use std::ffi::CString;
 use libc;
 
-/// Parsers can be streamed data to parse into a CommonMark AST.
+use std::mem;
+
+/// Parsers can be streamed data to parse into a CommonMark AST. Like
+/// `Document`, a `Parser` is `Send`, so parsing can happen on a worker
+/// thread.
+///
+/// The parser keeps a copy of the text it is fed, which becomes the
+/// `SourceMap` of the finished document.
 pub struct Parser {
     raw: *mut raw::cmark_parser,
+    source: String,
 }
 
 impl Parser {
     pub unsafe fn from_raw(raw: *mut raw::cmark_parser) -> Parser {
         Parser {
             raw: raw,
+            source: String::new(),
         }
     }
 
@@ -45,13 +53,17 @@ impl Parser {
     /// parser.feed("My *name* is **Inigo Montoya**");
     /// ```
     pub fn feed(&mut self, data: &str) {
+        // libcmark reads exactly `len` bytes and replaces any NUL characters
+        // with U+FFFD, so no terminator is needed.
         unsafe {
-            let cstr = CString::new(data).unwrap();
-            raw::cmark_parser_feed(self.raw, cstr.as_ptr(), cstr.to_bytes().len() as libc::size_t)
+            raw::cmark_parser_feed(self.raw, data.as_ptr() as *const libc::c_char,
+                                   data.len() as libc::size_t)
         }
+        self.source.push_str(data);
     }
 
-    /// Finish parsing and return the resulting node tree.
+    /// Finish parsing and return the resulting node tree, along with a
+    /// `SourceMap` of everything fed to the parser.
     ///
     /// # Examples
     ///
@@ -63,13 +75,21 @@ impl Parser {
     /// let doc = parser.finish();
     /// assert_eq!(doc.node_type(), NodeType::Document);
     /// ```
-    pub fn finish(&mut self) -> Node {
-        unsafe {
-            Node::from_raw(raw::cmark_parser_finish(self.raw), true)
-        }
+    pub fn finish(&mut self) -> Document {
+        let mut doc = unsafe {
+            Document::from_raw(raw::cmark_parser_finish(self.raw))
+        };
+        let source = mem::replace(&mut self.source, String::new());
+        doc.set_source_map(Some(SourceMap::new(&source)));
+        doc
     }
 }
 
+// A parser owns its libcmark state and the partial tree, none of which is
+// shared with other parsers, so it can be moved between threads between calls
+// to `feed`.
+unsafe impl Send for Parser {}
+
 impl Drop for Parser {
     fn drop(&mut self) {
         unsafe {
@@ -93,11 +113,12 @@ impl Drop for Parser {
 /// assert_eq!(strong.node_type(), NodeType::Strong);
 /// assert_eq!(strong.first_child().unwrap().literal(), "Hello, World");
 /// ```
-pub fn parse_document(doc: &str, options: CmarkOptions) -> Node {
-    unsafe {
-        let c_doc = CString::new(doc).unwrap();
-        Node::from_raw(raw::cmark_parse_document(c_doc.as_ptr(), c_doc.to_bytes().len() as libc::size_t, options.raw()), true)
-    }
+pub fn parse_document(doc: &str, options: CmarkOptions) -> Document {
+    let mut document = unsafe {
+        Document::from_raw(raw::cmark_parse_document(doc.as_ptr() as *const libc::c_char, doc.len() as libc::size_t, options.raw()))
+    };
+    document.set_source_map(Some(SourceMap::new(doc)));
+    document
 }
 
 #[cfg(test)]
@@ -118,4 +139,11 @@ mod test{
         let text = strong.first_child().unwrap();
         assert!(text.literal() == "Hi");
     }
+
+    #[test]
+    pub fn parse_nul() {
+        let tree = parse_document("a\0b", DEFAULT);
+        let text = tree.first_child().unwrap().first_child().unwrap();
+        assert_eq!(text.literal(), "a\u{FFFD}b");
+    }
 }
@@ -0,0 +1,318 @@
+use {Node, NodeRef, NodeType, EventType, Document, SourceMap};
+use util::Binding;
+
+use std::ops::Range;
+
+/// An index of the blocks in a tree by their source positions, for finding
+/// the node at a point in the source.
+///
+/// libcmark only records positions for block nodes. The index keeps the byte
+/// span of each block, in document order, so that finding the blocks at a
+/// point is a binary search followed by a walk up through at most the
+/// nesting depth of the tree. Inline nodes are found by re-scanning the
+/// source of the paragraph or header that contains them, matching each
+/// literal in turn. That scan is approximate where the literal differs from
+/// the source, such as with entities and backslash escapes, and the span of
+/// emphasis, links and images runs up to the start of whatever follows them.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, PositionIndex, NodeType, DEFAULT};
+///
+/// let doc = parse_document("# Title\n\nSome *emphasis*", DEFAULT);
+/// let index = PositionIndex::new(&doc, doc.source_map().unwrap());
+/// assert_eq!(index.node_at(1, 1).unwrap().node_type(), NodeType::Header);
+/// assert_eq!(index.node_at(3, 6).unwrap().node_type(), NodeType::Emph);
+/// assert_eq!(index.node_at(3, 9).unwrap().literal(), "emphasis");
+/// ```
+pub struct PositionIndex<'a> {
+    map: &'a SourceMap,
+    blocks: Vec<Entry<'a>>,
+}
+
+/// A node with its span, and the index of its nearest ancestor with a span.
+struct Entry<'a> {
+    node: NodeRef<'a>,
+    span: Range<usize>,
+    parent: Option<usize>,
+}
+
+impl<'a> PositionIndex<'a> {
+    /// Index the blocks below and including `root`, which must have been
+    /// parsed from the text in `map`.
+    pub fn new(root: &'a Node, map: &'a SourceMap) -> PositionIndex<'a> {
+        let mut blocks = Vec::new();
+        // The nearest indexed ancestor of each open container.
+        let mut open: Vec<Option<usize>> = Vec::new();
+
+        for (event, node) in root.iter() {
+            let node_type = node.node_type();
+            if !node_type.is_block() {
+                continue;
+            }
+            if event == EventType::Exit {
+                open.pop();
+                continue;
+            }
+
+            let parent = open.last().cloned().and_then(|parent| parent);
+            let index = node.source_span(map).map(|span| {
+                blocks.push(Entry {
+                    node: node.clone(),
+                    span: span,
+                    parent: parent,
+                });
+                blocks.len() - 1
+            });
+            if !node_type.is_leaf() {
+                open.push(index.or(parent));
+            }
+        }
+
+        PositionIndex {
+            map: map,
+            blocks: blocks,
+        }
+    }
+
+    /// Find the innermost node covering the character at `line` and
+    /// `column`, counted as libcmark does.
+    pub fn node_at(&self, line: i32, column: i32) -> Option<NodeRef<'a>> {
+        self.map.offset(line, column).and_then(|offset| self.node_at_offset(offset))
+    }
+
+    /// Find the innermost node covering the byte at `offset` in the source.
+    pub fn node_at_offset(&self, offset: usize) -> Option<NodeRef<'a>> {
+        deepest_containing(&self.blocks, offset).map(|index| {
+            let block = &self.blocks[index];
+            if !contains_inlines(block.node.node_type()) {
+                return block.node.clone();
+            }
+            let inlines = inline_entries(&block.node, &block.span, self.map.source());
+            match deepest_containing(&inlines, offset) {
+                Some(inline) => inlines[inline].node.clone(),
+                None => block.node.clone(),
+            }
+        })
+    }
+
+    /// Find the innermost nodes that overlap the byte `range` of the source,
+    /// in document order. A node is left out if one of its children also
+    /// overlaps the range. An empty range finds the node at that offset.
+    pub fn nodes_overlapping(&self, range: Range<usize>) -> Vec<NodeRef<'a>> {
+        if range.start >= range.end {
+            return self.node_at_offset(range.start).into_iter().collect();
+        }
+
+        // Any block before `first` that overlaps the range is an ancestor of
+        // the one at `first`, and so is not innermost.
+        let first = match deepest_containing(&self.blocks, range.start) {
+            Some(index) => index,
+            None => first_starting_at(&self.blocks, range.start),
+        };
+        let last = first_starting_at(&self.blocks, range.end);
+
+        let mut nodes = Vec::new();
+        for index in innermost_overlapping(&self.blocks, first, last, &range) {
+            let block = &self.blocks[index];
+            if !contains_inlines(block.node.node_type()) {
+                nodes.push(block.node.clone());
+                continue;
+            }
+            let inlines = inline_entries(&block.node, &block.span, self.map.source());
+            let found = innermost_overlapping(&inlines, 0, inlines.len(), &range);
+            if found.is_empty() {
+                nodes.push(block.node.clone());
+            }
+            nodes.extend(found.into_iter().map(|inline| inlines[inline].node.clone()));
+        }
+        nodes
+    }
+}
+
+impl Document {
+    /// Find the innermost node at `line` and `column` in the source this
+    /// document was parsed from, or `None` if it has no `SourceMap`. Each
+    /// call indexes the whole document, so use a `PositionIndex` to make
+    /// repeated queries.
+    pub fn node_at(&self, line: i32, column: i32) -> Option<NodeRef> {
+        self.source_map().and_then(|map| PositionIndex::new(self, map).node_at(line, column))
+    }
+
+    /// Find the innermost nodes overlapping the byte `range` of the source
+    /// this document was parsed from, as `PositionIndex::nodes_overlapping`
+    /// does.
+    pub fn nodes_overlapping(&self, range: Range<usize>) -> Vec<NodeRef> {
+        match self.source_map() {
+            Some(map) => PositionIndex::new(self, map).nodes_overlapping(range),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn contains_inlines(node_type: NodeType) -> bool {
+    node_type == NodeType::Paragraph || node_type == NodeType::Header
+}
+
+fn contains(span: &Range<usize>, offset: usize) -> bool {
+    span.start <= offset && offset < span.end
+}
+
+/// The index of the first entry starting at or after `offset`, or the number
+/// of entries if there is none.
+fn first_starting_at(entries: &[Entry], offset: usize) -> usize {
+    match entries.binary_search_by(|entry| entry.span.start.cmp(&offset)) {
+        Ok(mut index) => {
+            while index > 0 && entries[index - 1].span.start == offset {
+                index -= 1;
+            }
+            index
+        }
+        Err(index) => index,
+    }
+}
+
+/// Find the deepest entry containing `offset`. Entries are in document
+/// order, so their starts are sorted and nested entries come after their
+/// ancestors: the last entry starting at or before the offset is either the
+/// answer or a descendant of it.
+fn deepest_containing(entries: &[Entry], offset: usize) -> Option<usize> {
+    let mut index = match entries.binary_search_by(|entry| entry.span.start.cmp(&offset)) {
+        Ok(mut index) => {
+            while index + 1 < entries.len() && entries[index + 1].span.start == offset {
+                index += 1;
+            }
+            index
+        }
+        Err(0) => return None,
+        Err(index) => index - 1,
+    };
+
+    loop {
+        if contains(&entries[index].span, offset) {
+            return Some(index);
+        }
+        index = match entries[index].parent {
+            Some(parent) => parent,
+            None => return None,
+        };
+    }
+}
+
+/// The entries in `first..last` that overlap `range` and have no child that
+/// also does.
+fn innermost_overlapping(entries: &[Entry], first: usize, last: usize,
+                         range: &Range<usize>) -> Vec<usize> {
+    let overlaps = |entry: &Entry| entry.span.start < range.end && entry.span.end > range.start;
+    let mut has_child = vec![false; last - first];
+    for entry in &entries[first..last] {
+        match entry.parent {
+            Some(parent) if parent >= first && overlaps(entry) => has_child[parent - first] = true,
+            _ => (),
+        }
+    }
+    (first..last).filter(|&index| overlaps(&entries[index]) && !has_child[index - first])
+                 .collect()
+}
+
+/// Work out the spans of the inline nodes in `block` by finding each literal
+/// in its source, in order.
+fn inline_entries<'a>(block: &NodeRef<'a>, span: &Range<usize>, source: &str) -> Vec<Entry<'a>> {
+    let mut entries: Vec<Entry<'a>> = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    // Containers that have been exited, which end wherever the next inline
+    // starts.
+    let mut closing: Vec<usize> = Vec::new();
+    let mut cursor = span.start;
+
+    for (event, node) in block.iter() {
+        if node.raw() == block.raw() {
+            continue;
+        }
+        if event == EventType::Exit {
+            closing.extend(open.pop());
+            continue;
+        }
+
+        let found = match node.node_type() {
+            NodeType::Text | NodeType::Code | NodeType::InlineHtml =>
+                find(source, cursor..span.end, node.literal()),
+            NodeType::SoftBreak | NodeType::LineBreak =>
+                find_line_ending(source, cursor..span.end),
+            _ => None,
+        };
+        let start = found.as_ref().map_or(cursor, |found| found.start);
+        for index in closing.drain(..) {
+            entries[index].span.end = start;
+        }
+        if let Some(ref found) = found {
+            cursor = found.end;
+        }
+
+        entries.push(Entry {
+            node: node.clone(),
+            span: found.unwrap_or(cursor..cursor),
+            parent: open.last().cloned(),
+        });
+        if !node.node_type().is_leaf() {
+            open.push(entries.len() - 1);
+        }
+    }
+
+    for index in closing {
+        entries[index].span.end = span.end;
+    }
+    entries
+}
+
+fn find(source: &str, within: Range<usize>, literal: &str) -> Option<Range<usize>> {
+    if literal.is_empty() {
+        return None;
+    }
+    source[within.clone()].find(literal).map(|index| {
+        let start = within.start + index;
+        start..start + literal.len()
+    })
+}
+
+fn find_line_ending(source: &str, within: Range<usize>) -> Option<Range<usize>> {
+    source[within.clone()].find(|c| c == '\n' || c == '\r').map(|index| {
+        let start = within.start + index;
+        let len = if source[start..].starts_with("\r\n") { 2 } else { 1 };
+        start..start + len
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, NodeType, DEFAULT};
+
+    const SOURCE: &'static str = "# Title\n\nSome *emph* text\n\n> quote\n";
+
+    #[test]
+    fn test_node_at() {
+        let doc = parse_document(SOURCE, DEFAULT);
+        let index = PositionIndex::new(&doc, doc.source_map().unwrap());
+        assert_eq!(index.node_at(3, 7).unwrap().literal(), "emph");
+        assert_eq!(index.node_at(3, 11).unwrap().node_type(), NodeType::Emph);
+        assert_eq!(index.node_at(3, 12).unwrap().literal(), " text");
+        assert_eq!(index.node_at(5, 1).unwrap().node_type(), NodeType::BlockQuote);
+        assert_eq!(index.node_at(5, 3).unwrap().literal(), "quote");
+        assert!(index.node_at(9, 1).is_none());
+    }
+
+    #[test]
+    fn test_nodes_overlapping() {
+        let doc = parse_document(SOURCE, DEFAULT);
+        let literals: Vec<_> = doc.nodes_overlapping(12..30).iter()
+                                  .map(|node| node.literal().to_string())
+                                  .collect();
+        assert_eq!(literals, vec!["Some ", "emph", " text", "quote"]);
+
+        let nodes = doc.nodes_overlapping(0..1);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].node_type(), NodeType::Header);
+    }
+}
@@ -0,0 +1,579 @@
+use {Node, NodeRef, NodeType, ListType, DelimType};
+use node::handle;
+
+use std::error::Error;
+use std::fmt;
+
+/// A compiled CSS-style selector for finding nodes in a tree.
+///
+/// Selectors are built from the following pieces:
+///
+/// * Node type names, as returned by `Node::type_string` (`paragraph`,
+///   `header`, `code_block`, `link`...), or `*` for any node.
+/// * Attribute predicates: `[name]`, `[name=value]`, `[name^=prefix]`,
+///   `[name$=suffix]` and `[name*=substring]`. The attributes are `url`,
+///   `title`, `literal`, `fence_info`, `header_level`, `list_type` (`bullet`
+///   or `ordered`), `list_delim` (`period` or `paren`), `list_start` and
+///   `list_tight` (`true` or `false`). A predicate never matches a node its
+///   attribute does not apply to. Values may be quoted.
+/// * Pseudo-classes: `:first-child`, `:last-child`, `:only-child`, `:empty`
+///   and `:nth-child(n)`, counting from 1.
+/// * Combinators: whitespace for descendants, `>` for children, `+` for the
+///   next sibling and `~` for any later sibling.
+/// * Commas, to match any one of several selectors.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, Selector, DEFAULT};
+///
+/// let doc = parse_document("* [a](http://a.com)\n* [b](/b)", DEFAULT);
+/// let selector = Selector::parse("item > paragraph link[url^=http]").unwrap();
+/// let links: Vec<_> = doc.descendants().filter(|n| selector.matches(n)).collect();
+/// assert_eq!(links.len(), 1);
+/// assert_eq!(links[0].url(), "http://a.com");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Selector {
+    alternatives: Vec<Complex>,
+}
+
+/// An error describing why a selector could not be parsed.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SelectorError {
+    /// The byte offset in the selector at which the error was found.
+    pub position: usize,
+    /// What was wrong.
+    pub message: String,
+}
+
+impl fmt::Display for SelectorError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "invalid selector at {}: {}", self.position, self.message)
+    }
+}
+
+impl Error for SelectorError {
+    fn description(&self) -> &str {
+        "invalid selector"
+    }
+}
+
+/// A sequence of compound selectors joined by combinators, stored with the
+/// rightmost compound first since matching starts from the candidate node.
+#[derive(Clone, Debug)]
+struct Complex {
+    compounds: Vec<Compound>,
+    /// `combinators[i]` joins `compounds[i]` to `compounds[i + 1]`.
+    combinators: Vec<Combinator>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Combinator {
+    Descendant,
+    Child,
+    Adjacent,
+    Sibling,
+}
+
+#[derive(Clone, Debug)]
+struct Compound {
+    node_type: Option<NodeType>,
+    attributes: Vec<Attribute>,
+    pseudo_classes: Vec<PseudoClass>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum AttributeName {
+    Url,
+    Title,
+    Literal,
+    FenceInfo,
+    HeaderLevel,
+    ListType,
+    ListDelim,
+    ListStart,
+    ListTight,
+}
+
+#[derive(Clone, Debug)]
+enum Attribute {
+    Exists(AttributeName),
+    Equals(AttributeName, String),
+    Prefix(AttributeName, String),
+    Suffix(AttributeName, String),
+    Contains(AttributeName, String),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum PseudoClass {
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    Empty,
+    NthChild(usize),
+}
+
+impl Selector {
+    /// Compile a selector.
+    pub fn parse(selector: &str) -> Result<Selector, SelectorError> {
+        let mut parser = SelectorParser { input: selector, pos: 0 };
+        parser.selector_list()
+    }
+
+    /// Check whether `node` matches this selector.
+    pub fn matches(&self, node: &Node) -> bool {
+        let node = handle(node);
+        self.alternatives.iter().any(|complex| complex.matches(&node, 0))
+    }
+}
+
+impl Complex {
+    fn matches(&self, node: &NodeRef, index: usize) -> bool {
+        if !self.compounds[index].matches(node) {
+            return false;
+        }
+        if index + 1 == self.compounds.len() {
+            return true;
+        }
+
+        let next = index + 1;
+        match self.combinators[index] {
+            Combinator::Child =>
+                node.parent().map_or(false, |parent| self.matches(&parent, next)),
+            Combinator::Descendant =>
+                node.ancestors().any(|ancestor| self.matches(&ancestor, next)),
+            Combinator::Adjacent =>
+                node.previous().map_or(false, |sibling| self.matches(&sibling, next)),
+            Combinator::Sibling =>
+                node.preceding_siblings().any(|sibling| self.matches(&sibling, next)),
+        }
+    }
+}
+
+impl Compound {
+    fn matches(&self, node: &NodeRef) -> bool {
+        if let Some(node_type) = self.node_type {
+            if node.node_type() != node_type {
+                return false;
+            }
+        }
+        self.attributes.iter().all(|attribute| attribute.matches(node)) &&
+            self.pseudo_classes.iter().all(|pseudo| pseudo.matches(node))
+    }
+}
+
+impl Attribute {
+    fn matches(&self, node: &Node) -> bool {
+        match *self {
+            Attribute::Exists(name) =>
+                attribute_value(node, name).is_some(),
+            Attribute::Equals(name, ref expected) =>
+                attribute_value(node, name).map_or(false, |v| v == *expected),
+            Attribute::Prefix(name, ref expected) =>
+                attribute_value(node, name).map_or(false, |v| v.starts_with(&expected[..])),
+            Attribute::Suffix(name, ref expected) =>
+                attribute_value(node, name).map_or(false, |v| v.ends_with(&expected[..])),
+            Attribute::Contains(name, ref expected) =>
+                attribute_value(node, name).map_or(false, |v| v.contains(&expected[..])),
+        }
+    }
+}
+
+impl PseudoClass {
+    fn matches(&self, node: &NodeRef) -> bool {
+        match *self {
+            PseudoClass::FirstChild => node.previous().is_none(),
+            PseudoClass::LastChild => node.next().is_none(),
+            PseudoClass::OnlyChild => node.previous().is_none() && node.next().is_none(),
+            PseudoClass::Empty => node.first_child().is_none(),
+            PseudoClass::NthChild(n) => node.preceding_siblings().count() + 1 == n,
+        }
+    }
+}
+
+/// Read an attribute as a string, or `None` if it does not apply to the node.
+fn attribute_value(node: &Node, name: AttributeName) -> Option<String> {
+    let value = match name {
+        AttributeName::Url => node.try_url().map(|v| v.to_string()),
+        AttributeName::Title => node.try_title().map(|v| v.to_string()),
+        AttributeName::Literal => node.try_literal().map(|v| v.to_string()),
+        AttributeName::FenceInfo => node.try_fence_info().map(|v| v.to_string()),
+        AttributeName::HeaderLevel => node.try_header_level().map(|v| v.to_string()),
+        AttributeName::ListStart => node.try_list_start().map(|v| v.to_string()),
+        AttributeName::ListTight => node.try_list_tight().map(|v| v.to_string()),
+        AttributeName::ListType => node.try_list_type().map(|v| match v {
+            ListType::NoList => "none",
+            ListType::Bullet => "bullet",
+            ListType::Ordered => "ordered",
+        }.to_string()),
+        AttributeName::ListDelim => node.try_list_delim().map(|v| match v {
+            DelimType::NoDelim => "none",
+            DelimType::Period => "period",
+            DelimType::Paren => "paren",
+        }.to_string()),
+    };
+    value.ok()
+}
+
+fn node_type_named(name: &str) -> Option<NodeType> {
+    match name {
+        "document" => Some(NodeType::Document),
+        "block_quote" => Some(NodeType::BlockQuote),
+        "list" => Some(NodeType::List),
+        "item" => Some(NodeType::Item),
+        "code_block" => Some(NodeType::CodeBlock),
+        "html" => Some(NodeType::Html),
+        "paragraph" => Some(NodeType::Paragraph),
+        "header" => Some(NodeType::Header),
+        "hrule" => Some(NodeType::Hrule),
+        "text" => Some(NodeType::Text),
+        "softbreak" => Some(NodeType::SoftBreak),
+        "linebreak" => Some(NodeType::LineBreak),
+        "code" => Some(NodeType::Code),
+        "inline_html" => Some(NodeType::InlineHtml),
+        "emph" => Some(NodeType::Emph),
+        "strong" => Some(NodeType::Strong),
+        "link" => Some(NodeType::Link),
+        "image" => Some(NodeType::Image),
+        _ => None,
+    }
+}
+
+fn attribute_named(name: &str) -> Option<AttributeName> {
+    match name {
+        "url" => Some(AttributeName::Url),
+        "title" => Some(AttributeName::Title),
+        "literal" => Some(AttributeName::Literal),
+        "fence_info" => Some(AttributeName::FenceInfo),
+        "header_level" => Some(AttributeName::HeaderLevel),
+        "list_type" => Some(AttributeName::ListType),
+        "list_delim" => Some(AttributeName::ListDelim),
+        "list_start" => Some(AttributeName::ListStart),
+        "list_tight" => Some(AttributeName::ListTight),
+        _ => None,
+    }
+}
+
+struct SelectorParser<'s> {
+    input: &'s str,
+    pos: usize,
+}
+
+impl<'s> SelectorParser<'s> {
+    fn error<T>(&self, message: &str) -> Result<T, SelectorError> {
+        Err(SelectorError {
+            position: self.pos,
+            message: message.to_string(),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.pos += expected.len_utf8();
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), SelectorError> {
+        if self.eat(expected) {
+            Ok(())
+        }
+        else {
+            self.error(&format!("expected '{}'", expected))
+        }
+    }
+
+    /// Skip whitespace, returning whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.pos;
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.pos += c.len_utf8();
+        }
+        self.pos != start
+    }
+
+    fn ident(&mut self) -> Option<&'s str> {
+        let start = self.pos;
+        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_' || c == '-') {
+            self.pos += self.peek().unwrap().len_utf8();
+        }
+        if self.pos == start { None } else { Some(&self.input[start..self.pos]) }
+    }
+
+    fn selector_list(&mut self) -> Result<Selector, SelectorError> {
+        let mut alternatives = Vec::new();
+        loop {
+            self.skip_whitespace();
+            alternatives.push(try!(self.complex()));
+            if self.peek().is_none() {
+                return Ok(Selector { alternatives: alternatives });
+            }
+            try!(self.expect(','));
+        }
+    }
+
+    fn complex(&mut self) -> Result<Complex, SelectorError> {
+        let mut compounds = vec![try!(self.compound())];
+        let mut combinators = Vec::new();
+
+        loop {
+            let whitespace = self.skip_whitespace();
+            let combinator = match self.peek() {
+                None | Some(',') => break,
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::Adjacent,
+                Some('~') => Combinator::Sibling,
+                Some(_) if whitespace => Combinator::Descendant,
+                Some(_) => return self.error("expected a combinator"),
+            };
+            if combinator != Combinator::Descendant {
+                self.pos += 1;
+                self.skip_whitespace();
+            }
+            combinators.push(combinator);
+            compounds.push(try!(self.compound()));
+        }
+
+        compounds.reverse();
+        combinators.reverse();
+        Ok(Complex {
+            compounds: compounds,
+            combinators: combinators,
+        })
+    }
+
+    fn compound(&mut self) -> Result<Compound, SelectorError> {
+        let start = self.pos;
+        let node_type = if self.eat('*') {
+            None
+        }
+        else {
+            match self.ident() {
+                Some(name) => match node_type_named(name) {
+                    Some(node_type) => Some(node_type),
+                    None => {
+                        self.pos = start;
+                        return self.error(&format!("unknown node type '{}'", name));
+                    }
+                },
+                None => None,
+            }
+        };
+
+        let mut compound = Compound {
+            node_type: node_type,
+            attributes: Vec::new(),
+            pseudo_classes: Vec::new(),
+        };
+        loop {
+            if self.eat('[') {
+                compound.attributes.push(try!(self.attribute()));
+            }
+            else if self.eat(':') {
+                compound.pseudo_classes.push(try!(self.pseudo_class()));
+            }
+            else {
+                break;
+            }
+        }
+
+        if self.pos == start {
+            return self.error("expected a node type, '*', '[' or ':'");
+        }
+        Ok(compound)
+    }
+
+    fn attribute(&mut self) -> Result<Attribute, SelectorError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        let name = match self.ident() {
+            Some(name) => match attribute_named(name) {
+                Some(attribute) => attribute,
+                None => {
+                    self.pos = start;
+                    return self.error(&format!("unknown attribute '{}'", name));
+                }
+            },
+            None => return self.error("expected an attribute name"),
+        };
+        self.skip_whitespace();
+
+        if self.eat(']') {
+            return Ok(Attribute::Exists(name));
+        }
+
+        let operator = match self.peek() {
+            Some(c @ '^') | Some(c @ '$') | Some(c @ '*') => {
+                self.pos += 1;
+                c
+            }
+            _ => '=',
+        };
+        try!(self.expect('='));
+        self.skip_whitespace();
+        let value = try!(self.value());
+        self.skip_whitespace();
+        try!(self.expect(']'));
+
+        Ok(match operator {
+            '^' => Attribute::Prefix(name, value),
+            '$' => Attribute::Suffix(name, value),
+            '*' => Attribute::Contains(name, value),
+            _ => Attribute::Equals(name, value),
+        })
+    }
+
+    fn value(&mut self) -> Result<String, SelectorError> {
+        let quote = match self.peek() {
+            Some(c @ '"') | Some(c @ '\'') => c,
+            _ => {
+                // Unquoted values run up to the closing bracket.
+                let start = self.pos;
+                while self.peek().map_or(false, |c| c != ']' && !c.is_whitespace()) {
+                    self.pos += self.peek().unwrap().len_utf8();
+                }
+                if self.pos == start {
+                    return self.error("expected a value");
+                }
+                return Ok(self.input[start..self.pos].to_string());
+            }
+        };
+
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return self.error("unterminated string"),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(c) => {
+                            value.push(c);
+                            self.pos += c.len_utf8();
+                        }
+                        None => return self.error("unterminated string"),
+                    }
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn pseudo_class(&mut self) -> Result<PseudoClass, SelectorError> {
+        let start = self.pos;
+        match self.ident() {
+            Some("first-child") => Ok(PseudoClass::FirstChild),
+            Some("last-child") => Ok(PseudoClass::LastChild),
+            Some("only-child") => Ok(PseudoClass::OnlyChild),
+            Some("empty") => Ok(PseudoClass::Empty),
+            Some("nth-child") => {
+                try!(self.expect('('));
+                self.skip_whitespace();
+                let number_start = self.pos;
+                let n = self.ident().and_then(|n| n.parse().ok());
+                self.skip_whitespace();
+                try!(self.expect(')'));
+                match n {
+                    Some(n) if n > 0 => Ok(PseudoClass::NthChild(n)),
+                    _ => {
+                        self.pos = number_start;
+                        self.error("expected a positive number")
+                    }
+                }
+            }
+            Some(name) => {
+                self.pos = start;
+                self.error(&format!("unknown pseudo-class ':{}'", name))
+            }
+            None => self.error("expected a pseudo-class name"),
+        }
+    }
+}
+
+impl<'a> NodeRef<'a> {
+    /// Find every node below this one that matches `selector`, in document
+    /// order.
+    pub fn select(&self, selector: &str) -> Result<Vec<NodeRef<'a>>, SelectorError> {
+        let selector = try!(Selector::parse(selector));
+        Ok(self.descendants().filter(|node| selector.matches(node)).collect())
+    }
+
+    /// Find the first node below this one that matches `selector`.
+    pub fn select_first(&self, selector: &str) -> Result<Option<NodeRef<'a>>, SelectorError> {
+        let selector = try!(Selector::parse(selector));
+        Ok(self.descendants().find(|node| selector.matches(node)))
+    }
+}
+
+impl Node {
+    /// Find every node below this one that matches `selector`, in document
+    /// order. See `Selector` for the supported syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let doc = parse_document("## Setup\n\n```sh\nmake\n```\n\n```\nplain\n```", DEFAULT);
+    /// let blocks = doc.select("code_block[fence_info=sh]").unwrap();
+    /// assert_eq!(blocks.len(), 1);
+    /// assert_eq!(blocks[0].literal(), "make\n");
+    /// ```
+    pub fn select(&self, selector: &str) -> Result<Vec<NodeRef>, SelectorError> {
+        handle(self).select(selector)
+    }
+
+    /// Find the first node below this one that matches `selector`.
+    pub fn select_first(&self, selector: &str) -> Result<Option<NodeRef>, SelectorError> {
+        handle(self).select_first(selector)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, NodeType, DEFAULT};
+
+    #[test]
+    fn test_combinators() {
+        let doc = parse_document("# A\n\n> *b* c\n\n## D\n\ne", DEFAULT);
+        let types = |selector: &str| -> Vec<NodeType> {
+            doc.select(selector).unwrap().iter().map(|n| n.node_type()).collect()
+        };
+
+        assert_eq!(types("block_quote text").len(), 2);
+        assert_eq!(types("block_quote > text").len(), 0);
+        assert_eq!(types("header + block_quote"), vec![NodeType::BlockQuote]);
+        assert_eq!(types("header[header_level=1] ~ paragraph"), vec![NodeType::Paragraph]);
+        assert_eq!(types("paragraph > :first-child"), vec![NodeType::Emph, NodeType::Text]);
+        assert_eq!(types("header, hrule").len(), 2);
+        assert_eq!(types("header\u{3000}text").len(), 2);
+        assert_eq!(types("paragraph\u{a0}>\u{2003}emph"), vec![NodeType::Emph]);
+        assert_eq!(types("*:nth-child(2)"),
+                   vec![NodeType::BlockQuote, NodeType::Text]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Selector::parse("paragraph > bogus").unwrap_err().position, 12);
+        assert_eq!(Selector::parse("link[href]").unwrap_err().position, 5);
+        assert!(Selector::parse("link[url='x]").is_err());
+        assert!(Selector::parse("item:nth-child(0)").is_err());
+        assert!(Selector::parse("").is_err());
+    }
+}
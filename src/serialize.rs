@@ -0,0 +1,177 @@
+use {Block, Inline, Document, NodeType};
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser;
+use serde::de::Error;
+
+/// The version of the serialized document schema. It is increased whenever a
+/// change to the schema would stop older data from deserializing correctly.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The outermost object of a serialized `Document`.
+#[derive(Serialize)]
+struct EnvelopeRef<'a> {
+    version: u32,
+    document: &'a Block,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    version: u32,
+    document: Block,
+}
+
+impl Serialize for Document {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let document = try!(Block::from_node(self).map_err(ser::Error::custom));
+        EnvelopeRef {
+            version: SCHEMA_VERSION,
+            document: &document,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Document {
+    /// Rebuild a document from its serialized form. Source positions are read
+    /// but, as with `Block::to_node`, cannot be restored on the new nodes.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Document, D::Error> {
+        let envelope = try!(Envelope::deserialize(deserializer));
+        if envelope.version != SCHEMA_VERSION {
+            return Err(D::Error::custom(format_args!(
+                "unsupported document schema version {}, expected {}",
+                envelope.version, SCHEMA_VERSION)));
+        }
+        if envelope.document.node_type() != NodeType::Document {
+            return Err(D::Error::custom("the root of a serialized document must be a document node"));
+        }
+        envelope.document.to_document().map_err(D::Error::custom)
+    }
+}
+
+// `Inline` has tuple and unit variants, which serde cannot tag internally, so
+// it goes through these mirrors to get the same `{"type": ...}` objects as
+// `Block`.
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum InlineRef<'a> {
+    Text { literal: &'a str },
+    Softbreak,
+    Linebreak,
+    Code { literal: &'a str },
+    InlineHtml { literal: &'a str },
+    Emph { children: &'a [Inline] },
+    Strong { children: &'a [Inline] },
+    Link { url: &'a str, title: &'a str, children: &'a [Inline] },
+    Image { url: &'a str, title: &'a str, children: &'a [Inline] },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum InlineRepr {
+    Text { literal: String },
+    Softbreak,
+    Linebreak,
+    Code { literal: String },
+    InlineHtml { literal: String },
+    Emph { children: Vec<Inline> },
+    Strong { children: Vec<Inline> },
+    Link {
+        url: String,
+        #[serde(default)]
+        title: String,
+        children: Vec<Inline>,
+    },
+    Image {
+        url: String,
+        #[serde(default)]
+        title: String,
+        children: Vec<Inline>,
+    },
+}
+
+impl Serialize for Inline {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match *self {
+            Inline::Text(ref literal) => InlineRef::Text { literal: literal },
+            Inline::SoftBreak => InlineRef::Softbreak,
+            Inline::LineBreak => InlineRef::Linebreak,
+            Inline::Code(ref literal) => InlineRef::Code { literal: literal },
+            Inline::InlineHtml(ref literal) => InlineRef::InlineHtml { literal: literal },
+            Inline::Emph(ref children) => InlineRef::Emph { children: children },
+            Inline::Strong(ref children) => InlineRef::Strong { children: children },
+            Inline::Link { ref url, ref title, ref children } =>
+                InlineRef::Link { url: url, title: title, children: children },
+            Inline::Image { ref url, ref title, ref children } =>
+                InlineRef::Image { url: url, title: title, children: children },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Inline {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Inline, D::Error> {
+        InlineRepr::deserialize(deserializer).map(|repr| match repr {
+            InlineRepr::Text { literal } => Inline::Text(literal),
+            InlineRepr::Softbreak => Inline::SoftBreak,
+            InlineRepr::Linebreak => Inline::LineBreak,
+            InlineRepr::Code { literal } => Inline::Code(literal),
+            InlineRepr::InlineHtml { literal } => Inline::InlineHtml(literal),
+            InlineRepr::Emph { children } => Inline::Emph(children),
+            InlineRepr::Strong { children } => Inline::Strong(children),
+            InlineRepr::Link { url, title, children } =>
+                Inline::Link { url: url, title: title, children: children },
+            InlineRepr::Image { url, title, children } =>
+                Inline::Image { url: url, title: title, children: children },
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{parse_document, render_html, Document, DEFAULT};
+    use serde_json;
+
+    #[test]
+    fn test_json_round_trip() {
+        let source = "# Title\n\n1) *one* `two`\n2) [three](/3 \"3\")  \n   ![four](/4)\n\n\
+                      ```sh\nls\n```\n\n<p>five</p>\n\n> ---\n";
+        let doc = parse_document(source, DEFAULT);
+        let json = serde_json::to_string(&doc).unwrap();
+        assert!(json.starts_with("{\"version\":1,\"document\":{\"type\":\"document\""));
+        assert!(json.contains("{\"type\":\"header\",\"header_level\":1,\"children\":\
+                               [{\"type\":\"text\",\"literal\":\"Title\"}],\
+                               \"pos\":{\"start_line\":1,\"start_column\":1,\
+                               \"end_line\":1,\"end_column\":7}}"));
+        assert!(json.contains("\"list_type\":\"ordered\",\"list_delim\":\"paren\""));
+
+        let back: Document = serde_json::from_str(&json).unwrap();
+        assert_eq!(render_html(&back, DEFAULT), render_html(&doc, DEFAULT));
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let wrong_version = "{\"version\":2,\"document\":{\"type\":\"document\",\"children\":[]}}";
+        let err = serde_json::from_str::<Document>(wrong_version).unwrap_err();
+        assert!(err.to_string().contains("unsupported document schema version 2"));
+
+        let item = "{\"version\":1,\"document\":{\"type\":\"document\",\"children\":\
+                    [{\"type\":\"item\",\"children\":[]}]}}";
+        let err = serde_json::from_str::<Document>(item).unwrap_err();
+        assert!(err.to_string().contains("Document nodes cannot contain Item nodes"));
+
+        let minimal = "{\"version\":1,\"document\":{\"type\":\"document\",\"children\":\
+                       [{\"type\":\"paragraph\",\"children\":\
+                       [{\"type\":\"link\",\"url\":\"/\",\"children\":\
+                       [{\"type\":\"softbreak\"}]}]}]}}";
+        let doc: Document = serde_json::from_str(minimal).unwrap();
+        assert_eq!(render_html(&doc, DEFAULT), "<p><a href=\"/\">\n</a></p>\n");
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let doc = parse_document(&"> ".repeat(50000), DEFAULT);
+        let err = serde_json::to_string(&doc).unwrap_err();
+        assert!(err.to_string().contains("BlockQuote node is nested more than 256 levels deep"));
+    }
+}
@@ -0,0 +1,210 @@
+use Node;
+
+use std::ops::Range;
+
+/// The source text of a document, indexed by line so that the positions
+/// libcmark records on nodes can be turned back into byte ranges.
+///
+/// libcmark counts lines from 1, and columns from 1 in bytes from the start
+/// of the line. A tab is a single column, as is each byte of a multi-byte
+/// UTF-8 character. Lines may end in `\n`, `\r\n` or a lone `\r`, and line
+/// endings are never part of a line.
+///
+/// The text is stored as libcmark saw it: a leading byte order mark is
+/// dropped and NUL characters are replaced with U+FFFD, so the offsets on the
+/// affected lines match the parsed positions rather than the original input.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, DEFAULT};
+///
+/// let doc = parse_document("# Title\r\n\r\nSome *text*\r\n", DEFAULT);
+/// let map = doc.source_map().unwrap();
+/// let para = doc.last_child().unwrap();
+/// assert_eq!(para.source_span(map), Some(11..22));
+/// assert_eq!(para.source_text(map), Some("Some *text*"));
+/// ```
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct SourceMap {
+    source: String,
+    /// The byte range of each line, without its line ending.
+    lines: Vec<Range<usize>>,
+}
+
+impl SourceMap {
+    /// Index `source`, which should be the full text given to the parser.
+    pub fn new(source: &str) -> SourceMap {
+        let source = if source.starts_with('\u{FEFF}') { &source[3..] } else { source };
+        let source = source.replace('\0', "\u{FFFD}");
+
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let bytes = source.as_bytes();
+        let mut index = 0;
+        while index < bytes.len() {
+            match bytes[index] {
+                b'\n' => {
+                    lines.push(start..index);
+                    start = index + 1;
+                }
+                b'\r' => {
+                    lines.push(start..index);
+                    if bytes.get(index + 1) == Some(&b'\n') {
+                        index += 1;
+                    }
+                    start = index + 1;
+                }
+                _ => (),
+            }
+            index += 1;
+        }
+        if start < bytes.len() {
+            lines.push(start..bytes.len());
+        }
+
+        SourceMap {
+            source: source,
+            lines: lines,
+        }
+    }
+
+    /// Get the indexed source text.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Get the number of lines in the source.
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Get the text of a line, counting from 1, without its line ending.
+    pub fn line(&self, line: i32) -> Option<&str> {
+        self.line_range(line).map(|range| &self.source[range])
+    }
+
+    fn line_range(&self, line: i32) -> Option<Range<usize>> {
+        if line < 1 {
+            return None;
+        }
+        self.lines.get(line as usize - 1).cloned()
+    }
+
+    /// Get the byte offset of the character at `line` and `column`. Columns
+    /// past the end of the line are clamped to the line ending, and a column
+    /// of 0, which libcmark uses for some empty ends, means the start of the
+    /// line.
+    pub fn offset(&self, line: i32, column: i32) -> Option<usize> {
+        self.line_range(line).map(|range| {
+            let column = if column < 1 { 0 } else { column as usize - 1 };
+            self.char_start(range.start + column.min(range.end - range.start))
+        })
+    }
+
+    /// Get the byte range covered by a node's positions. The end position is
+    /// inclusive, as in libcmark, and the range is widened as needed so that
+    /// it never splits a UTF-8 character.
+    pub fn span(&self, start_line: i32, start_column: i32,
+                end_line: i32, end_column: i32) -> Option<Range<usize>> {
+        let start = match self.offset(start_line, start_column) {
+            Some(start) => start,
+            None => return None,
+        };
+        self.line_range(end_line).map(|range| {
+            let column = if end_column < 0 { 0 } else { end_column as usize };
+            let end = self.char_end(range.start + column.min(range.end - range.start));
+            start..end.max(start)
+        })
+    }
+
+    /// Get the line and column of the byte at `offset`. Offsets inside a line
+    /// ending belong to the line they end.
+    pub fn position(&self, offset: usize) -> Option<(i32, i32)> {
+        if offset > self.source.len() {
+            return None;
+        }
+        // The last line whose start is at or before the offset.
+        let line = match self.lines.binary_search_by(|range| range.start.cmp(&offset)) {
+            Ok(index) => index,
+            Err(0) => return Some((1, 1)),
+            Err(index) => index - 1,
+        };
+        Some((line as i32 + 1, (offset - self.lines[line].start) as i32 + 1))
+    }
+
+    fn char_start(&self, mut offset: usize) -> usize {
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    fn char_end(&self, mut offset: usize) -> usize {
+        while !self.source.is_char_boundary(offset) {
+            offset += 1;
+        }
+        offset
+    }
+}
+
+impl Node {
+    /// Get the byte range of the source text this node was parsed from, or
+    /// `None` if libcmark did not record a position for it. Only block nodes
+    /// have positions; inline nodes and nodes built by hand do not.
+    pub fn source_span(&self, map: &SourceMap) -> Option<Range<usize>> {
+        if self.start_line() == 0 {
+            return None;
+        }
+        map.span(self.start_line(), self.start_column(), self.end_line(), self.end_column())
+    }
+
+    /// Get the source text this node was parsed from, as with `source_span`.
+    pub fn source_text<'a>(&self, map: &'a SourceMap) -> Option<&'a str> {
+        self.source_span(map).map(|span| &map.source()[span])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, Parser, Document, DEFAULT};
+
+    #[test]
+    fn test_line_endings() {
+        let map = SourceMap::new("a\r\nb\rc\n\nd");
+        assert_eq!(map.line_count(), 5);
+        assert_eq!(map.line(2), Some("b"));
+        assert_eq!(map.line(4), Some(""));
+        assert_eq!(map.line(6), None);
+        assert_eq!(map.offset(3, 1), Some(5));
+        assert_eq!(map.span(1, 1, 1, 9), Some(0..1));
+        assert_eq!(map.position(6), Some((3, 2)));
+        assert_eq!(map.position(8), Some((5, 1)));
+    }
+
+    #[test]
+    fn test_columns_are_bytes() {
+        let map = SourceMap::new("\tx\nnaïve\0");
+        assert_eq!(map.offset(1, 2), Some(1));
+        assert_eq!(map.source(), "\tx\nnaïve\u{FFFD}");
+        // Column 4 is the second byte of the ï, so the span covers all of it.
+        assert_eq!(map.span(2, 1, 2, 4).map(|span| &map.source()[span]), Some("naï"));
+        assert_eq!(map.offset(2, 4), Some(5));
+    }
+
+    #[test]
+    fn test_parser_source_map() {
+        let mut parser = Parser::new(DEFAULT);
+        parser.feed("> quote\n");
+        parser.feed("\n    code\n");
+        let doc = parser.finish();
+        let map = doc.source_map().unwrap();
+        let quote = doc.first_child().unwrap();
+        assert_eq!(quote.source_text(map), Some("> quote"));
+        assert_eq!(quote.first_child().unwrap().first_child().unwrap().source_span(map), None);
+
+        assert!(parse_document("", DEFAULT).source_map().is_some());
+        assert!(Document::new().source_map().is_none());
+    }
+}
@@ -0,0 +1,132 @@
+use {Node, NodeType, EventType};
+
+/// Controls how `Node::text_content_with` and `Node::block_text_content`
+/// turn a subtree into plain text.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct TextOptions {
+    /// The text used for a soft line break. Defaults to a single space.
+    pub softbreak: String,
+    /// The text used for a hard line break. Defaults to a newline.
+    pub linebreak: String,
+    /// Whether to include raw HTML, both inline and block, as it appears in
+    /// the source. Defaults to `false`.
+    pub include_html: bool,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            softbreak: " ".to_string(),
+            linebreak: "\n".to_string(),
+            include_html: false,
+        }
+    }
+}
+
+impl Node {
+    /// Get the plain text of this node and its descendants, using the default
+    /// `TextOptions`. The text of links and emphasis is kept, and images
+    /// contribute their alt text. Blocks are run together without any
+    /// separator; see `block_text_content` to keep them apart.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, DEFAULT};
+    ///
+    /// let doc = parse_document("# The *`rcmark`* guide ![logo](/logo.png)", DEFAULT);
+    /// let header = doc.first_child().unwrap();
+    /// assert_eq!(header.text_content(), "The rcmark guide logo");
+    /// ```
+    pub fn text_content(&self) -> String {
+        self.text_content_with(&TextOptions::default())
+    }
+
+    /// Get the plain text of this node and its descendants, as
+    /// `text_content` does, using `options`.
+    pub fn text_content_with(&self, options: &TextOptions) -> String {
+        collect_text(self, options, false)
+    }
+
+    /// Get the plain text of this node and its descendants, with a newline
+    /// between each paragraph, list item, header and other block.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rcmark::{parse_document, TextOptions, DEFAULT};
+    ///
+    /// let doc = parse_document("Intro\ntext\n\n* one\n* two", DEFAULT);
+    /// assert_eq!(doc.block_text_content(&TextOptions::default()),
+    ///            "Intro text\none\ntwo");
+    /// ```
+    pub fn block_text_content(&self, options: &TextOptions) -> String {
+        collect_text(self, options, true)
+    }
+}
+
+fn collect_text(root: &Node, options: &TextOptions, separate_blocks: bool) -> String {
+    let mut out = String::new();
+    // Set when a block has ended, so that a newline goes in before any text
+    // from the next one.
+    let mut block_ended = false;
+
+    for (event, node) in root.iter() {
+        let node_type = node.node_type();
+        let text = match (event, node_type) {
+            (EventType::Enter, NodeType::Text) | (EventType::Enter, NodeType::Code) =>
+                Some(node.literal()),
+            (EventType::Enter, NodeType::CodeBlock) =>
+                Some(node.literal().trim_end_matches('\n')),
+            (EventType::Enter, NodeType::Html) if options.include_html =>
+                Some(node.literal().trim_end_matches('\n')),
+            (EventType::Enter, NodeType::InlineHtml) if options.include_html =>
+                Some(node.literal()),
+            (EventType::Enter, NodeType::SoftBreak) => Some(&options.softbreak[..]),
+            (EventType::Enter, NodeType::LineBreak) => Some(&options.linebreak[..]),
+            _ => None,
+        };
+
+        if let Some(text) = text {
+            if block_ended && !out.is_empty() {
+                out.push('\n');
+            }
+            block_ended = false;
+            out.push_str(text);
+        }
+
+        let ends_block = node_type.is_block() &&
+            (event == EventType::Exit || node_type.is_leaf());
+        if separate_blocks && ends_block {
+            block_ended = true;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, DEFAULT};
+
+    #[test]
+    fn test_options() {
+        let doc = parse_document("a\\\nb\nc <br> `d`\n\n<div>e</div>\n\n    f\n", DEFAULT);
+        assert_eq!(doc.text_content(), "a\nb c  df");
+
+        let options = TextOptions {
+            softbreak: "/".to_string(),
+            linebreak: "|".to_string(),
+            include_html: true,
+        };
+        assert_eq!(doc.text_content_with(&options), "a|b/c <br> d<div>e</div>f");
+        assert_eq!(doc.block_text_content(&options), "a|b/c <br> d\n<div>e</div>\nf");
+    }
+
+    #[test]
+    fn test_nested_blocks() {
+        let doc = parse_document("> * one\n>\n>   more\n> * two\n\n---\n\nend", DEFAULT);
+        assert_eq!(doc.block_text_content(&TextOptions::default()), "one\nmore\ntwo\nend");
+    }
+}
@@ -0,0 +1,362 @@
+use {raw, Node, NodeMut, NodeError};
+use util::Binding;
+
+/// What a `Transform` wants done with the node it was given.
+pub enum Action {
+    /// Leave the node as it is.
+    Keep,
+    /// Keep the node, which the transform has edited in place, for example by
+    /// changing its URL or header level. Only affects the `PipelineReport`.
+    Modified,
+    /// Remove the node and its children from the tree.
+    Remove,
+    /// Put these nodes where the node was, and remove it.
+    Replace(Vec<Node>),
+    /// Move the node's children up into its parent, and remove the node.
+    Unwrap,
+}
+
+/// One pass of a `Pipeline`.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{NodeMut, NodeType, Transform, Action};
+///
+/// /// Turn every level 1 header into a level 2 header.
+/// struct DemoteHeaders;
+///
+/// impl Transform for DemoteHeaders {
+///     fn name(&self) -> &str { "demote-headers" }
+///
+///     fn transform(&mut self, node: &mut NodeMut) -> Action {
+///         if node.node_type() == NodeType::Header && node.header_level() == 1 {
+///             node.set_header_level(2).unwrap();
+///             Action::Modified
+///         }
+///         else {
+///             Action::Keep
+///         }
+///     }
+/// }
+/// ```
+pub trait Transform {
+    /// A name for this pass, used in the `PipelineReport`.
+    fn name(&self) -> &str;
+
+    /// Decide what to do with `node`. The node may be edited in place.
+    fn transform(&mut self, node: &mut NodeMut) -> Action;
+}
+
+/// Runs several transforms over a tree in a single traversal.
+///
+/// The pipeline makes the following guarantees about ordering:
+///
+/// * Nodes are visited in document order, parents before their children.
+///   The root node itself is not visited.
+/// * Each node is given to the transforms in the order they were added, and
+///   each transform sees any in-place edits made by the ones before it.
+/// * Once a transform removes, replaces or unwraps a node, no later
+///   transform sees that node.
+/// * Nodes put into the tree by `Action::Replace`, and their descendants, are
+///   only given to the transforms added after the one that produced them, so
+///   a transform never sees its own output.
+/// * Children lifted out by `Action::Unwrap` have not been visited yet, so
+///   they are given to the same transforms as their former parent.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, render_html, Pipeline, Transform, Action,
+///              NodeMut, NodeType, DEFAULT};
+///
+/// struct StripHtml;
+///
+/// impl Transform for StripHtml {
+///     fn name(&self) -> &str { "strip-html" }
+///
+///     fn transform(&mut self, node: &mut NodeMut) -> Action {
+///         match node.node_type() {
+///             NodeType::Html | NodeType::InlineHtml => Action::Remove,
+///             _ => Action::Keep,
+///         }
+///     }
+/// }
+///
+/// struct UnwrapEmphasis;
+///
+/// impl Transform for UnwrapEmphasis {
+///     fn name(&self) -> &str { "unwrap-emphasis" }
+///
+///     fn transform(&mut self, node: &mut NodeMut) -> Action {
+///         if node.node_type() == NodeType::Emph { Action::Unwrap } else { Action::Keep }
+///     }
+/// }
+///
+/// let mut doc = parse_document("Some <b>bold</b> *claims*", DEFAULT);
+/// let report = Pipeline::new()
+///     .add(StripHtml)
+///     .add(UnwrapEmphasis)
+///     .run(doc.root_mut())
+///     .unwrap();
+///
+/// assert_eq!(render_html(&doc, DEFAULT), "<p>Some bold claims</p>\n");
+/// assert_eq!(report.passes[0].removed, 2);
+/// assert_eq!(report.passes[1].unwrapped, 1);
+/// ```
+pub struct Pipeline {
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+/// What a `Pipeline` run changed, with one entry per transform in the order
+/// they were added.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PipelineReport {
+    pub passes: Vec<PassReport>,
+}
+
+/// The number of nodes one transform acted on.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct PassReport {
+    pub name: String,
+    pub modified: usize,
+    pub removed: usize,
+    pub replaced: usize,
+    pub unwrapped: usize,
+}
+
+impl Pipeline {
+    /// Create a pipeline with no transforms.
+    pub fn new() -> Pipeline {
+        Pipeline {
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Add a transform, to run after all of those already added.
+    pub fn add<T: Transform + 'static>(&mut self, transform: T) -> &mut Pipeline {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Run every transform over the descendants of `root`.
+    ///
+    /// # Errors
+    ///
+    /// If a transform asks for a replacement or unwrapping that would nest
+    /// nodes invalidly, such as replacing a paragraph with a text node, the
+    /// run stops and returns `NodeError::InvalidChild`. Changes made before
+    /// that point are kept.
+    pub fn run(&mut self, root: NodeMut) -> Result<PipelineReport, NodeError> {
+        let mut report = PipelineReport {
+            passes: self.transforms.iter().map(|transform| PassReport {
+                name: transform.name().to_string(),
+                ..PassReport::default()
+            }).collect(),
+        };
+
+        // Walk the tree with an explicit stack, as `deep_clone` does, so that
+        // deeply nested documents cannot overflow the call stack. Each entry
+        // is a node still to be visited and the index of the first transform
+        // it is given to, with the next node to visit on top. A transform can
+        // only free nodes below the one it is given, never its siblings, so
+        // the entries stay valid while the nodes before them are transformed.
+        let mut pending = Vec::new();
+        push_children(&mut pending, &root, 0);
+        while let Some((node, first)) = pending.pop() {
+            let node = unsafe { NodeMut::from_raw(node) };
+            try!(self.run_node(node, first, &mut pending, &mut report));
+        }
+        Ok(report)
+    }
+
+    /// Give `node` to the transforms from index `first` on, then queue up
+    /// whichever nodes should be visited next in its place.
+    fn run_node(&mut self, mut node: NodeMut, first: usize, pending: &mut Vec<Pending>,
+                report: &mut PipelineReport) -> Result<(), NodeError> {
+        for index in first..self.transforms.len() {
+            match self.transforms[index].transform(&mut node) {
+                Action::Keep => (),
+                Action::Modified => {
+                    report.passes[index].modified += 1;
+                }
+                Action::Remove => {
+                    report.passes[index].removed += 1;
+                    try!(node.detach());
+                    return Ok(());
+                }
+                Action::Replace(replacements) => {
+                    let inserted = try!(insert_before(&mut node, replacements));
+                    report.passes[index].replaced += 1;
+                    try!(node.detach());
+                    pending.extend(inserted.into_iter().rev().map(|raw| (raw, index + 1)));
+                    return Ok(());
+                }
+                Action::Unwrap => {
+                    // Inserting a node unlinks it from its old place first.
+                    let children: Vec<Node> = node.children().map(|child| {
+                        unsafe { Node::from_raw(child.raw(), false) }
+                    }).collect();
+                    let lifted = try!(insert_before(&mut node, children));
+                    report.passes[index].unwrapped += 1;
+                    try!(node.detach());
+                    pending.extend(lifted.into_iter().rev().map(|raw| (raw, first)));
+                    return Ok(());
+                }
+            }
+        }
+
+        push_children(pending, &node, first);
+        Ok(())
+    }
+}
+
+/// A node waiting to be visited, and the index of the first transform that
+/// should see it.
+type Pending = (*mut raw::cmark_node, usize);
+
+/// Queue the children of `parent` so that they are visited in order.
+fn push_children(pending: &mut Vec<Pending>, parent: &Node, first: usize) {
+    let start = pending.len();
+    pending.extend(parent.children().map(|child| (child.raw(), first)));
+    pending[start..].reverse();
+}
+
+/// Insert `nodes` before `node`, after checking that its parent can hold all
+/// of them. Returns the inserted nodes.
+fn insert_before(node: &mut NodeMut, nodes: Vec<Node>)
+                 -> Result<Vec<*mut raw::cmark_node>, NodeError> {
+    let parent_type = match node.parent() {
+        Some(parent) => parent.node_type(),
+        None => return Err(NodeError::NoParent { node_type: node.node_type() }),
+    };
+    let invalid = |child: &Node| NodeError::InvalidChild {
+        parent: parent_type,
+        child: child.node_type(),
+    };
+    if let Some(bad) = nodes.iter().find(|n| !parent_type.can_contain(n.node_type())) {
+        return Err(invalid(bad));
+    }
+
+    let mut inserted = Vec::new();
+    for new_node in nodes {
+        let raw = new_node.raw();
+        if let Err(new_node) = node.insert_before(new_node) {
+            return Err(invalid(&new_node));
+        }
+        inserted.push(raw);
+    }
+    Ok(inserted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, render_html, Node, NodeMut, NodeType, NodeError, DEFAULT};
+
+    /// Replaces every emphasis with a fresh emphasis around copies of its
+    /// children. Would never finish if it were shown its own output.
+    struct Rewrap;
+
+    impl Transform for Rewrap {
+        fn name(&self) -> &str { "rewrap" }
+
+        fn transform(&mut self, node: &mut NodeMut) -> Action {
+            if node.node_type() != NodeType::Emph {
+                return Action::Keep;
+            }
+            let mut emph = Node::new(NodeType::Emph);
+            for child in node.children() {
+                emph.append_child(child.deep_clone()).unwrap();
+            }
+            Action::Replace(vec![emph])
+        }
+    }
+
+    /// Reports every node of one type as modified.
+    struct Mark(NodeType);
+
+    impl Transform for Mark {
+        fn name(&self) -> &str { "mark" }
+
+        fn transform(&mut self, node: &mut NodeMut) -> Action {
+            if node.node_type() == self.0 { Action::Modified } else { Action::Keep }
+        }
+    }
+
+    struct UnwrapQuotes;
+
+    impl Transform for UnwrapQuotes {
+        fn name(&self) -> &str { "unwrap-quotes" }
+
+        fn transform(&mut self, node: &mut NodeMut) -> Action {
+            if node.node_type() == NodeType::BlockQuote { Action::Unwrap } else { Action::Keep }
+        }
+    }
+
+    #[test]
+    fn test_replacement_not_revisited() {
+        let mut doc = parse_document("*a* and *b*", DEFAULT);
+        let report = Pipeline::new()
+            .add(Rewrap)
+            .add(Rewrap)
+            .add(Mark(NodeType::Text))
+            .run(doc.root_mut())
+            .unwrap();
+        assert_eq!(render_html(&doc, DEFAULT), "<p><em>a</em> and <em>b</em></p>\n");
+        assert_eq!(report.passes[0].replaced, 2);
+        assert_eq!(report.passes[1].replaced, 2);
+        assert_eq!(report.passes[2].modified, 3);
+    }
+
+    #[test]
+    fn test_unwrapped_children_visited() {
+        let mut doc = parse_document("> a\n>\n> b\n\nc", DEFAULT);
+        let report = Pipeline::new()
+            .add(Mark(NodeType::Paragraph))
+            .add(UnwrapQuotes)
+            .run(doc.root_mut())
+            .unwrap();
+        assert_eq!(render_html(&doc, DEFAULT), "<p>a</p>\n<p>b</p>\n<p>c</p>\n");
+        assert_eq!(report.passes[0], PassReport {
+            name: "mark".to_string(),
+            modified: 3,
+            ..PassReport::default()
+        });
+        assert_eq!(report.passes[1].unwrapped, 1);
+    }
+
+    #[test]
+    fn test_deeply_nested() {
+        let mut doc = parse_document(&"> ".repeat(50000), DEFAULT);
+        let report = Pipeline::new()
+            .add(Mark(NodeType::BlockQuote))
+            .run(doc.root_mut())
+            .unwrap();
+        assert_eq!(report.passes[0].modified, 50000);
+    }
+
+    #[test]
+    fn test_invalid_replacement() {
+        struct TextForParagraph;
+
+        impl Transform for TextForParagraph {
+            fn name(&self) -> &str { "bad" }
+
+            fn transform(&mut self, node: &mut NodeMut) -> Action {
+                if node.node_type() == NodeType::Paragraph {
+                    Action::Replace(vec![Node::new(NodeType::Text)])
+                }
+                else {
+                    Action::Keep
+                }
+            }
+        }
+
+        let mut doc = parse_document("para", DEFAULT);
+        let result = Pipeline::new().add(TextForParagraph).run(doc.root_mut());
+        assert_eq!(result, Err(NodeError::InvalidChild { parent: NodeType::Document,
+                                                         child: NodeType::Text }));
+        assert_eq!(render_html(&doc, DEFAULT), "<p>para</p>\n");
+    }
+}
@@ -1,4 +1,4 @@
-use std::ffi::{CString, CStr};
+use std::ffi::{CString, CStr, NulError};
 use std::str;
 use libc;
 
@@ -42,19 +42,24 @@ impl Binding for bool {
     fn raw(&self) -> libc::c_int {
         match *self {
             true  => 1,
-            false => 1,
+            false => 0,
         }
     }
 }
 
-impl<'a> Binding for &'a str {
-    type Raw = *const libc::c_char;
-
-    unsafe fn from_raw(raw: *const libc::c_char) -> &'a str {
+/// Borrow a string owned by libcmark. A null pointer is treated as the empty
+/// string.
+pub unsafe fn str_from_raw<'a>(raw: *const libc::c_char) -> &'a str {
+    if raw.is_null() {
+        ""
+    }
+    else {
         str::from_utf8(CStr::from_ptr(raw).to_bytes()).unwrap()
     }
+}
 
-    fn raw(&self) -> *const libc::c_char {
-        CString::new(*self).unwrap().as_ptr()
-    }
+/// Copy a string into a NUL-terminated buffer for passing to libcmark. The
+/// buffer must outlive the call that uses its pointer.
+pub fn to_c_string(value: &str) -> Result<CString, NulError> {
+    CString::new(value)
 }
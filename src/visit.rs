@@ -0,0 +1,218 @@
+use {Node, NodeRef, NodeType, EventType};
+
+/// What a `Visitor` wants to happen after one of its callbacks returns.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum VisitControl {
+    /// Carry on with the walk.
+    Continue,
+    /// Do not visit the children of the node being entered. The node's exit
+    /// callback is still called. Has the same effect as `Continue` when
+    /// returned from an exit callback or for a node without children.
+    SkipChildren,
+    /// End the walk immediately.
+    Stop,
+}
+
+/// Callbacks for `Node::walk`, one per node type. Each is called with
+/// `EventType::Enter` when the walk reaches a node and, for nodes that may
+/// have children, with `EventType::Exit` once its children are done. Nodes
+/// that can never have children (see `NodeType::is_leaf`) are only entered.
+///
+/// Every callback defaults to doing nothing and returning `Continue`, so a
+/// visitor only needs to implement the ones it cares about.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, Visitor, VisitControl, NodeRef, EventType, DEFAULT};
+///
+/// struct LinkCollector {
+///     urls: Vec<String>,
+/// }
+///
+/// impl Visitor for LinkCollector {
+///     fn visit_link(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+///         if event == EventType::Enter {
+///             self.urls.push(node.url().to_string());
+///         }
+///         VisitControl::Continue
+///     }
+///
+///     fn visit_code_block(&mut self, _: &NodeRef, _: EventType) -> VisitControl {
+///         VisitControl::Stop
+///     }
+/// }
+///
+/// let doc = parse_document("[a](/a) and [b](/b)\n\n    code\n\n[c](/c)", DEFAULT);
+/// let mut collector = LinkCollector { urls: vec![] };
+/// doc.walk(&mut collector);
+/// assert_eq!(collector.urls, vec!["/a", "/b"]);
+/// ```
+#[allow(unused_variables)]
+pub trait Visitor {
+    fn visit_document(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_block_quote(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_list(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_item(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_code_block(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_html(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_paragraph(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_header(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_hrule(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_text(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_softbreak(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_linebreak(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_code(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_inline_html(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_emph(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_strong(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_link(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+
+    fn visit_image(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+        VisitControl::Continue
+    }
+}
+
+fn dispatch<V: Visitor>(visitor: &mut V, node: &NodeRef, event: EventType) -> VisitControl {
+    match node.node_type() {
+        NodeType::None => VisitControl::Continue,
+        NodeType::Document => visitor.visit_document(node, event),
+        NodeType::BlockQuote => visitor.visit_block_quote(node, event),
+        NodeType::List => visitor.visit_list(node, event),
+        NodeType::Item => visitor.visit_item(node, event),
+        NodeType::CodeBlock => visitor.visit_code_block(node, event),
+        NodeType::Html => visitor.visit_html(node, event),
+        NodeType::Paragraph => visitor.visit_paragraph(node, event),
+        NodeType::Header => visitor.visit_header(node, event),
+        NodeType::Hrule => visitor.visit_hrule(node, event),
+        NodeType::Text => visitor.visit_text(node, event),
+        NodeType::SoftBreak => visitor.visit_softbreak(node, event),
+        NodeType::LineBreak => visitor.visit_linebreak(node, event),
+        NodeType::Code => visitor.visit_code(node, event),
+        NodeType::InlineHtml => visitor.visit_inline_html(node, event),
+        NodeType::Emph => visitor.visit_emph(node, event),
+        NodeType::Strong => visitor.visit_strong(node, event),
+        NodeType::Link => visitor.visit_link(node, event),
+        NodeType::Image => visitor.visit_image(node, event),
+    }
+}
+
+impl Node {
+    /// Walk this node and its descendants in document order, calling the
+    /// `visitor` callback for each node's type on every enter and exit.
+    /// Returns `VisitControl::Stop` if the visitor ended the walk early, and
+    /// `VisitControl::Continue` otherwise.
+    pub fn walk<V: Visitor>(&self, visitor: &mut V) -> VisitControl {
+        let mut iter = self.iter();
+
+        while let Some((event, node)) = iter.next() {
+            match dispatch(visitor, &node, event) {
+                VisitControl::Continue => (),
+                VisitControl::Stop => return VisitControl::Stop,
+                VisitControl::SkipChildren => {
+                    if event == EventType::Enter && !node.node_type().is_leaf() {
+                        // Jump straight to the exit event, which the iterator
+                        // then treats as already produced.
+                        iter.reset(&node, EventType::Exit);
+                        if dispatch(visitor, &node, EventType::Exit) == VisitControl::Stop {
+                            return VisitControl::Stop;
+                        }
+                    }
+                }
+            }
+        }
+
+        VisitControl::Continue
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, NodeRef, EventType, DEFAULT};
+
+    struct Recorder {
+        events: Vec<(String, EventType)>,
+    }
+
+    impl Visitor for Recorder {
+        fn visit_block_quote(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+            self.events.push((node.type_string().to_string(), event));
+            VisitControl::SkipChildren
+        }
+
+        fn visit_paragraph(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+            self.events.push((node.type_string().to_string(), event));
+            VisitControl::Continue
+        }
+
+        fn visit_text(&mut self, node: &NodeRef, event: EventType) -> VisitControl {
+            self.events.push((node.literal().to_string(), event));
+            VisitControl::Continue
+        }
+    }
+
+    #[test]
+    fn test_skip_children() {
+        let doc = parse_document("> quoted\n\nplain", DEFAULT);
+        let mut recorder = Recorder { events: vec![] };
+        assert_eq!(doc.walk(&mut recorder), VisitControl::Continue);
+        assert_eq!(recorder.events, vec![
+            ("block_quote".to_string(), EventType::Enter),
+            ("block_quote".to_string(), EventType::Exit),
+            ("paragraph".to_string(), EventType::Enter),
+            ("plain".to_string(), EventType::Enter),
+            ("paragraph".to_string(), EventType::Exit),
+        ]);
+    }
+}
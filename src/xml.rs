@@ -0,0 +1,455 @@
+use {Node, NodeType, ListType, DelimType, Document, NodeError};
+use builder::append_checked;
+
+use std::error::Error;
+use std::fmt;
+
+/// An error from `parse_xml`, with the line of the XML input it occurred on.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct XmlError {
+    /// The line of the input, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(fmt, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for XmlError {
+    fn description(&self) -> &str {
+        "invalid CommonMark XML"
+    }
+}
+
+/// Read a tree in the CommonMark.dtd XML format written by `render_xml`.
+///
+/// Every element is named after the `type_string` of a node type, and the
+/// `level`, `type`, `start`, `tight`, `delimiter` (or `delim`), `info`,
+/// `destination` and `title` attributes set the matching node properties.
+/// `sourcepos` attributes are checked, but libcmark has no way to set source
+/// positions, so the returned nodes have none. Other attributes are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use rcmark::{parse_document, parse_xml, render_xml, render_html, DEFAULT};
+///
+/// let doc = parse_document("# Hello\n\n1) *world*", DEFAULT);
+/// let xml = render_xml(&doc, DEFAULT);
+/// let copy = parse_xml(&xml).unwrap();
+/// assert_eq!(render_html(&copy, DEFAULT), render_html(&doc, DEFAULT));
+///
+/// let err = parse_xml("<document>\n  <text>stray</text>\n</document>").unwrap_err();
+/// assert_eq!(err.line, 2);
+/// assert_eq!(err.message, "Document nodes cannot contain Text nodes");
+/// ```
+pub fn parse_xml(xml: &str) -> Result<Document, XmlError> {
+    let mut reader = Reader {
+        input: xml,
+        pos: 0,
+        line: 1,
+    };
+    let mut stack: Vec<Open> = Vec::new();
+    let mut root = None;
+
+    while let Some(token) = try!(reader.next_token()) {
+        match token {
+            Token::Start { name, attributes, empty, line } => {
+                if root.is_some() {
+                    return error(line, format!("<{}> after the end of the root element", name));
+                }
+                let open = try!(open_element(name, &attributes, line));
+                if empty {
+                    try!(close(&mut stack, &mut root, open, line));
+                }
+                else {
+                    stack.push(open);
+                }
+            }
+            Token::Text { text, line } => {
+                match stack.last_mut() {
+                    Some(&mut Open { literal: Some(ref mut literal), .. }) => {
+                        literal.push_str(&text);
+                        continue;
+                    }
+                    _ if text.chars().all(char::is_whitespace) => continue,
+                    Some(open) => {
+                        return error(line, format!("unexpected text inside <{}>", open.name));
+                    }
+                    None => return error(line, "text outside of the root element".to_string()),
+                }
+            }
+            Token::End { name, line } => {
+                let open = match stack.pop() {
+                    Some(open) => open,
+                    None => return error(line, format!("unexpected </{}>", name)),
+                };
+                if open.name != name {
+                    return error(line, format!("expected </{}>, found </{}>", open.name, name));
+                }
+                try!(close(&mut stack, &mut root, open, line));
+            }
+        }
+    }
+
+    if let Some(open) = stack.last() {
+        return error(reader.line, format!("<{}> is never closed", open.name));
+    }
+    match root {
+        Some(root) => Ok(Document::from(root)),
+        None => error(reader.line, "no root element".to_string()),
+    }
+}
+
+fn error<T>(line: usize, message: String) -> Result<T, XmlError> {
+    Err(XmlError {
+        line: line,
+        message: message,
+    })
+}
+
+fn node_error(line: usize, err: NodeError) -> XmlError {
+    XmlError {
+        line: line,
+        message: err.to_string(),
+    }
+}
+
+/// An element that has been started but not yet attached to its parent.
+struct Open {
+    name: String,
+    node: Node,
+    /// The text content so far, for elements whose content is a literal.
+    literal: Option<String>,
+}
+
+fn open_element(name: String, attributes: &[(String, String)], line: usize)
+                -> Result<Open, XmlError> {
+    let node_type = match &name[..] {
+        "document" => NodeType::Document,
+        "block_quote" => NodeType::BlockQuote,
+        "list" => NodeType::List,
+        "item" => NodeType::Item,
+        "code_block" => NodeType::CodeBlock,
+        "html" => NodeType::Html,
+        "paragraph" => NodeType::Paragraph,
+        "header" => NodeType::Header,
+        "hrule" => NodeType::Hrule,
+        "text" => NodeType::Text,
+        "softbreak" => NodeType::SoftBreak,
+        "linebreak" => NodeType::LineBreak,
+        "code" => NodeType::Code,
+        "inline_html" => NodeType::InlineHtml,
+        "emph" => NodeType::Emph,
+        "strong" => NodeType::Strong,
+        "link" => NodeType::Link,
+        "image" => NodeType::Image,
+        _ => return error(line, format!("unknown element <{}>", name)),
+    };
+
+    let mut node = Node::new(node_type);
+    for &(ref attribute, ref value) in attributes {
+        try!(set_attribute(&mut node, attribute, value).map_err(|message| XmlError {
+            line: line,
+            message: format!("invalid {} attribute on <{}>: {}", attribute, name, message),
+        }));
+    }
+
+    let literal = match node_type {
+        NodeType::Text | NodeType::Code | NodeType::CodeBlock |
+        NodeType::Html | NodeType::InlineHtml => Some(String::new()),
+        _ => None,
+    };
+    Ok(Open {
+        name: name,
+        node: node,
+        literal: literal,
+    })
+}
+
+fn set_attribute(node: &mut Node, attribute: &str, value: &str) -> Result<(), String> {
+    let node_type = node.node_type();
+    let result = match (node_type, attribute) {
+        (NodeType::Header, "level") => node.set_header_level(try!(parse_number(value))),
+        (NodeType::List, "type") => node.set_list_type(match value {
+            "bullet" => ListType::Bullet,
+            "ordered" => ListType::Ordered,
+            _ => return Err(format!("unknown list type {:?}", value)),
+        }),
+        (NodeType::List, "start") => node.set_list_start(try!(parse_number(value))),
+        (NodeType::List, "tight") => node.set_list_tight(match value {
+            "true" => true,
+            "false" => false,
+            _ => return Err(format!("expected true or false, found {:?}", value)),
+        }),
+        (NodeType::List, "delimiter") | (NodeType::List, "delim") => node.set_list_delim(match value {
+            "period" => DelimType::Period,
+            "paren" => DelimType::Paren,
+            _ => return Err(format!("unknown delimiter {:?}", value)),
+        }),
+        (NodeType::CodeBlock, "info") => node.set_fence_info(value),
+        (NodeType::Link, "destination") | (NodeType::Image, "destination") => node.set_url(value),
+        (NodeType::Link, "title") | (NodeType::Image, "title") => node.set_title(value),
+        (_, "sourcepos") => return parse_sourcepos(value).map(|_| ()),
+        _ => Ok(()),
+    };
+    result.map_err(|err| err.to_string())
+}
+
+fn parse_number(value: &str) -> Result<i32, String> {
+    value.parse().map_err(|_| format!("expected a number, found {:?}", value))
+}
+
+/// Check a position of the form `1:1-2:10`.
+fn parse_sourcepos(value: &str) -> Result<[i32; 4], String> {
+    let invalid = || format!("expected line:column-line:column, found {:?}", value);
+    let mut numbers = [0; 4];
+    let mut parts = value.split(|c| c == ':' || c == '-');
+    for number in numbers.iter_mut() {
+        *number = try!(parts.next().and_then(|part| part.parse().ok()).ok_or_else(&invalid));
+    }
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    Ok(numbers)
+}
+
+/// Finish `open`, attaching its node to the enclosing element or making it
+/// the root.
+fn close(stack: &mut Vec<Open>, root: &mut Option<Node>, open: Open, line: usize)
+         -> Result<(), XmlError> {
+    let Open { mut node, literal, .. } = open;
+    if let Some(literal) = literal {
+        try!(node.set_literal(&literal).map_err(|err| node_error(line, err)));
+    }
+    match stack.last_mut() {
+        Some(parent) => append_checked(&mut parent.node, node).map_err(|err| node_error(line, err)),
+        None => {
+            *root = Some(node);
+            Ok(())
+        }
+    }
+}
+
+enum Token {
+    Start {
+        name: String,
+        attributes: Vec<(String, String)>,
+        empty: bool,
+        line: usize,
+    },
+    End {
+        name: String,
+        line: usize,
+    },
+    Text {
+        text: String,
+        line: usize,
+    },
+}
+
+/// Splits XML into tags and text, skipping the declaration, doctype,
+/// comments and processing instructions.
+struct Reader<'a> {
+    input: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn advance(&mut self, len: usize) {
+        self.line += self.input[self.pos..self.pos + len].matches('\n').count();
+        self.pos += len;
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<(), XmlError> {
+        match self.rest().find(end) {
+            Some(index) => {
+                self.advance(index + end.len());
+                Ok(())
+            }
+            None => error(self.line, format!("missing {:?}", end)),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let len = self.rest().len() - self.rest().trim_start().len();
+        self.advance(len);
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), XmlError> {
+        if self.rest().starts_with(text) {
+            self.advance(text.len());
+            Ok(())
+        }
+        else {
+            error(self.line, format!("expected {:?}", text))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, XmlError> {
+        let len = self.rest().find(|c: char| {
+            !(c.is_alphanumeric() || c == '_' || c == '-' || c == ':' || c == '.')
+        }).unwrap_or(self.rest().len());
+        if len == 0 {
+            return error(self.line, "expected a name".to_string());
+        }
+        let name = self.rest()[..len].to_string();
+        self.advance(len);
+        Ok(name)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, XmlError> {
+        loop {
+            let line = self.line;
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Ok(None);
+            }
+            else if rest.starts_with("<?") {
+                try!(self.skip_past("?>"));
+            }
+            else if rest.starts_with("<!--") {
+                try!(self.skip_past("-->"));
+            }
+            else if rest.starts_with("<!") {
+                try!(self.skip_past(">"));
+            }
+            else if rest.starts_with("</") {
+                self.advance(2);
+                let name = try!(self.name());
+                self.skip_whitespace();
+                try!(self.expect(">"));
+                return Ok(Some(Token::End {
+                    name: name,
+                    line: line,
+                }));
+            }
+            else if rest.starts_with("<") {
+                self.advance(1);
+                return self.start_tag(line).map(Some);
+            }
+            else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                let text = try!(unescape(&rest[..len]).map_err(|message| XmlError {
+                    line: line,
+                    message: message,
+                }));
+                self.advance(len);
+                return Ok(Some(Token::Text {
+                    text: text,
+                    line: line,
+                }));
+            }
+        }
+    }
+
+    fn start_tag(&mut self, line: usize) -> Result<Token, XmlError> {
+        let name = try!(self.name());
+        let mut attributes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.advance(2);
+                return Ok(Token::Start { name: name, attributes: attributes, empty: true, line: line });
+            }
+            if self.rest().starts_with(">") {
+                self.advance(1);
+                return Ok(Token::Start { name: name, attributes: attributes, empty: false, line: line });
+            }
+
+            let attribute = try!(self.name());
+            self.skip_whitespace();
+            try!(self.expect("="));
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => quote,
+                _ => return error(self.line, format!("expected a quoted value for {}", attribute)),
+            };
+            self.advance(1);
+            let len = match self.rest().find(quote) {
+                Some(len) => len,
+                None => return error(self.line, format!("unterminated value for {}", attribute)),
+            };
+            let value = try!(unescape(&self.rest()[..len]).map_err(|message| XmlError {
+                line: self.line,
+                message: message,
+            }));
+            self.advance(len + 1);
+            attributes.push((attribute, value));
+        }
+    }
+}
+
+/// Replace the predefined entities and character references in `text`.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => return Err("unterminated entity".to_string()),
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if entity.starts_with("#x") || entity.starts_with("#X") =>
+                u32::from_str_radix(&entity[2..], 16).ok().and_then(::std::char::from_u32),
+            _ if entity.starts_with('#') =>
+                entity[1..].parse().ok().and_then(::std::char::from_u32),
+            _ => None,
+        };
+        match c {
+            Some(c) => out.push(c),
+            None => return Err(format!("unknown entity &{};", entity)),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::super::{parse_document, render_xml, Positions, DEFAULT, SOURCEPOS};
+
+    #[test]
+    fn test_round_trip() {
+        let source = "# A &amp; B\n\n> * one\n>\n>   two\n\n3) `<code>`  \n<span>x</span> \
+                      [link](/url \"it's\") ![alt](/img.png)\n\n\
+                      ~~~ rust\nlet x = \"\";\n~~~\n\n<div>\n\n***\n";
+        let doc = parse_document(source, DEFAULT);
+        for &options in &[DEFAULT, SOURCEPOS] {
+            let copy = parse_xml(&render_xml(&doc, options)).unwrap();
+            assert!(doc.structural_eq(&copy, Positions::Ignore));
+            assert_eq!(render_xml(&copy, DEFAULT), render_xml(&doc, DEFAULT));
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let line = |xml: &str| parse_xml(xml).unwrap_err().line;
+        assert_eq!(line("<document>\n<paragraph>\n</document>"), 3);
+        assert_eq!(line("<document>\n\n<header level=\"seven\" />"), 3);
+        assert_eq!(line("<document>\n<list sourcepos=\"1:1\">"), 2);
+        assert_eq!(line("<document>\n<html>&nbsp;</html>\n</document>"), 2);
+        assert_eq!(line("<document>\n</document>\n<document/>"), 3);
+
+        let err = parse_xml("<document><para /></document>").unwrap_err();
+        assert_eq!(err.to_string(), "line 1: unknown element <para>");
+    }
+}
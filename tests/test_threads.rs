@@ -0,0 +1,48 @@
+extern crate rcmark;
+
+use std::sync::mpsc;
+use std::thread;
+
+#[test]
+fn test_parse_and_render_on_different_threads() {
+    let (tx, rx) = mpsc::channel();
+
+    let parsers: Vec<_> = (0..4).map(|i| {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let mut parser = rcmark::Parser::new(rcmark::DEFAULT);
+            parser.feed(&format!("# Part {}\n", i));
+            parser.feed("Some *text*");
+            let doc = parser.finish();
+            tx.send((i, doc)).unwrap();
+        })
+    }).collect();
+    drop(tx);
+
+    let renderer = thread::spawn(move || {
+        let mut rendered: Vec<_> = rx.iter().map(|(i, doc)| {
+            (i, rcmark::render_html(&doc, rcmark::DEFAULT))
+        }).collect();
+        rendered.sort();
+        rendered
+    });
+
+    for parser in parsers {
+        parser.join().unwrap();
+    }
+    let rendered = renderer.join().unwrap();
+
+    assert_eq!(rendered.len(), 4);
+    for (i, html) in rendered {
+        assert_eq!(html, format!("<h1>Part {}</h1>\n<p>Some <em>text</em></p>\n", i));
+    }
+}
+
+#[test]
+fn test_send_parser() {
+    let mut parser = rcmark::Parser::new(rcmark::DEFAULT);
+    parser.feed("**Hello**");
+    let doc = thread::spawn(move || parser.finish()).join().unwrap();
+    assert_eq!(rcmark::render_html(&doc, rcmark::DEFAULT),
+               "<p><strong>Hello</strong></p>\n");
+}